@@ -0,0 +1,162 @@
+/*
+    delta modulation channel, $4010-$4013, see https://www.nesdev.org/wiki/APU_DMC
+
+    $4010   IL-- RRRR   irq enable, loop, rate index
+    $4011   -DDD DDDD   direct load of the output level
+    $4012   AAAA AAAA   sample address, $C000 + A * 64
+    $4013   LLLL LLLL   sample length, L * 16 + 1 bytes
+
+    sample bytes are fetched by the memory reader through the cpu bus, which stalls the cpu, so the
+    fetch itself is done by the apu with access to the whole emulator, see apu::run_cycle
+*/
+
+// ntsc timer periods in cpu cycles
+static RATE_TABLE: [u16; 0x10] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+#[derive(Clone)]
+pub struct DMC {
+    pub irq_enabled: bool,
+    pub irq_flag: bool,
+    pub looping: bool,
+    pub timer: u16,
+    pub timer_period: u16,
+    pub output_level: u8,
+
+    // memory reader
+    pub sample_address: u16,
+    pub sample_length: u16,
+    pub current_address: u16,
+    pub bytes_remaining: u16,
+    pub sample_buffer: Option<u8>,
+
+    // output unit
+    pub shift_register: u8,
+    pub bits_remaining: u8,
+    pub silence: bool,
+}
+
+impl Default for DMC {
+    fn default() -> DMC {
+        DMC {
+            irq_enabled: false,
+            irq_flag: false,
+            looping: false,
+            timer: 0,
+            timer_period: RATE_TABLE[0],
+            output_level: 0,
+
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+        }
+    }
+}
+
+impl DMC {
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                self.looping = value & 0x40 != 0;
+                self.timer_period = RATE_TABLE[(value & 0x0F) as usize];
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+            },
+            1 => {
+                self.output_level = value & 0x7F;
+            },
+            2 => {
+                self.sample_address = 0xC000 | ((value as u16) << 6);
+            },
+            3 => {
+                self.sample_length = ((value as u16) << 4) + 1;
+            },
+            _ => {}
+        }
+    }
+
+    // $4015 bit 4
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq_flag = false;
+
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // true when the memory reader wants the byte at current_address
+    pub fn needs_sample(&self) -> bool {
+        return self.sample_buffer.is_none() && self.bytes_remaining > 0;
+    }
+
+    pub fn load_sample(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+
+        // the address wraps around to $8000 rather than $0000
+        self.current_address = if self.current_address == 0xFFFF { 0x8000 } else { self.current_address + 1 };
+        self.bytes_remaining -= 1;
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    // clocked every cpu cycle, the rate table is in cpu cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period - 1;
+
+        if !self.silence {
+            // the level moves by 2 in the direction of the current bit, but never wraps
+            if self.shift_register & 0x1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                },
+                None => self.silence = true,
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        return self.output_level;
+    }
+}
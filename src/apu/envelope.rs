@@ -0,0 +1,91 @@
+/*
+    envelope and length counter units shared by the pulse and noise channels (the triangle only uses the length counter)
+    see https://www.nesdev.org/wiki/APU_Envelope and https://www.nesdev.org/wiki/APU_Length_Counter
+*/
+
+pub static LENGTH_TABLE: [u8; 0x20] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+#[derive(Default, Clone)]
+pub struct Envelope {
+    pub start: bool,
+    pub looping: bool, // shares its bit with the length counter halt flag
+    pub constant_volume: bool,
+    pub volume: u8, // constant volume, or the divider period when decaying
+    pub divider: u8,
+    pub decay: u8,
+}
+
+impl Envelope {
+    // --LC VVVV from the channel's first register
+    pub fn write_control(&mut self, value: u8) {
+        self.looping = value & 0x20 != 0;
+        self.constant_volume = value & 0x10 != 0;
+        self.volume = value & 0x0F;
+    }
+
+    // clocked by the frame counter every quarter frame
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+            return;
+        }
+
+        if self.divider > 0 {
+            self.divider -= 1;
+            return;
+        }
+
+        self.divider = self.volume;
+        if self.decay > 0 {
+            self.decay -= 1;
+        } else if self.looping {
+            self.decay = 15;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            return self.volume;
+        }
+
+        return self.decay;
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct LengthCounter {
+    pub enabled: bool, // from $4015, a disabled counter is forced to 0
+    pub halt: bool,
+    pub counter: u8,
+}
+
+impl LengthCounter {
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    // clocked by the frame counter every half frame
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        return self.counter > 0;
+    }
+}
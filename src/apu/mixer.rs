@@ -0,0 +1,24 @@
+use crate::apu;
+
+/*
+    non linear mixing of the channel outputs, see https://www.nesdev.org/wiki/APU_Mixer
+
+    pulse_out = 95.88 / ((8128 / (pulse1 + pulse2)) + 100)
+    tnd_out = 159.79 / (1 / ((triangle / 8227) + (noise / 12241) + (dmc / 22638)) + 100)
+
+    output is in the range 0.0 to roughly 1.0
+*/
+
+pub fn mix(apu: &apu::APU) -> f32 {
+    let pulse = (apu.pulse1.output() + apu.pulse2.output()) as f32;
+    let triangle = apu.triangle.output() as f32;
+    let noise = apu.noise.output() as f32;
+    let dmc = apu.dmc.output() as f32;
+
+    let pulse_out = if pulse == 0.0 { 0.0 } else { 95.88 / ((8128.0 / pulse) + 100.0) };
+
+    let tnd = (triangle / 8227.0) + (noise / 12241.0) + (dmc / 22638.0);
+    let tnd_out = if tnd == 0.0 { 0.0 } else { 159.79 / ((1.0 / tnd) + 100.0) };
+
+    return pulse_out + tnd_out;
+}
@@ -0,0 +1,266 @@
+mod envelope;
+mod pulse;
+mod triangle;
+mod noise;
+mod dmc;
+mod mixer;
+mod resampler;
+use std::fmt;
+use crate::config;
+use crate::cpu;
+/*
+    2A03 audio processing unit, see https://www.nesdev.org/wiki/APU
+
+    $4000-$4003	Pulse 1
+    $4004-$4007	Pulse 2
+    $4008-$400B	Triangle
+    $400C-$400F	Noise
+    $4010-$4013	DMC
+    $4015	    Channel enable (write) / status (read)
+    $4017	    Frame counter
+
+    the apu is clocked once per cpu cycle, the pulse timers only tick on every other one (an apu cycle)
+*/
+
+pub static CLOCK_RATE: f64 = 1789773.0; // ntsc cpu clock
+pub static SAMPLE_RATE: f64 = 44100.0;
+
+// cpu cycles the dmc memory reader stalls the cpu for when it fetches a sample byte
+static DMC_DMA_STALL: u32 = 4;
+
+/*
+    frame counter, see https://www.nesdev.org/wiki/APU_Frame_Counter
+
+    steps in cpu cycles since the sequence was reset (ntsc)
+    4-step: 7457 quarter, 14913 quarter + half, 22371 quarter, 29829 quarter + half + irq, reset at 29830
+    5-step: 7457 quarter, 14913 quarter + half, 22371 quarter, 37281 quarter + half, reset at 37282
+    the irq flag is also raised on the cycles either side of 29829 in 4-step mode
+*/
+static FRAME_STEP_1: u32 = 7457;
+static FRAME_STEP_2: u32 = 14913;
+static FRAME_STEP_3: u32 = 22371;
+static FRAME_STEP_4: u32 = 29829;
+static FRAME_STEP_5: u32 = 37281;
+
+#[derive(Clone)]
+pub struct APU {
+    pub pulse1: pulse::Pulse,
+    pub pulse2: pulse::Pulse,
+    pub triangle: triangle::Triangle,
+    pub noise: noise::Noise,
+    pub dmc: dmc::DMC,
+    pub cycle: u64,
+
+    // frame counter
+    pub five_step_mode: bool,
+    pub irq_inhibit: bool,
+    pub frame_irq_flag: bool,
+    pub frame_cycle: u32,
+    pub frame_reset_delay: u8, // cpu cycles until a $4017 write resets the sequence, 0 when none is pending
+
+    // output
+    pub resampler: resampler::Resampler,
+    pub amplitude: f32, // last mixer output passed to the resampler
+    pub frame_clock: u32, // cpu cycles since the last end_frame
+}
+
+impl Default for APU {
+    fn default() -> APU {
+        APU {
+            pulse1: pulse::Pulse::new(true),
+            pulse2: pulse::Pulse::new(false),
+            triangle: triangle::Triangle::default(),
+            noise: noise::Noise::default(),
+            dmc: dmc::DMC::default(),
+            cycle: 0,
+
+            five_step_mode: false,
+            irq_inhibit: false,
+            frame_irq_flag: false,
+            frame_cycle: 0,
+            frame_reset_delay: 0,
+
+            resampler: resampler::Resampler::new(CLOCK_RATE, SAMPLE_RATE),
+            amplitude: 0.0,
+            frame_clock: 0,
+        }
+    }
+}
+
+impl fmt::Display for APU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle: {}", self.cycle)
+    }
+}
+
+pub fn run_cycle(emulator: &mut config::Emulator) {
+    clock_frame_counter(emulator);
+
+    if emulator.apu.cycle % 2 == 0 {
+        emulator.apu.pulse1.clock_timer();
+        emulator.apu.pulse2.clock_timer();
+    }
+
+    emulator.apu.triangle.clock_timer();
+    emulator.apu.noise.clock_timer();
+    emulator.apu.dmc.clock_timer();
+
+    if emulator.apu.dmc.needs_sample() {
+        let address = emulator.apu.dmc.current_address;
+        let value = cpu::read_u8(emulator, cpu::mapped_address, address.into());
+        emulator.apu.dmc.load_sample(value);
+        emulator.cpu.cycle += DMC_DMA_STALL;
+    }
+
+    // only changes in the output are fed to the resampler
+    let amplitude = mixer::mix(&emulator.apu);
+    if amplitude != emulator.apu.amplitude {
+        let delta = amplitude - emulator.apu.amplitude;
+        emulator.apu.resampler.add_delta(emulator.apu.frame_clock, delta);
+        emulator.apu.amplitude = amplitude;
+    }
+
+    emulator.apu.cycle += 1;
+    emulator.apu.frame_clock += 1;
+}
+
+// closes off the audio for the cycles run since the last call, appending the finished samples
+pub fn end_frame(emulator: &mut config::Emulator, samples: &mut Vec<f32>) {
+    emulator.apu.resampler.end_frame(emulator.apu.frame_clock);
+    emulator.apu.frame_clock = 0;
+    emulator.apu.resampler.read_samples(samples);
+}
+
+// dynamic rate control, nudges the number of samples produced per cpu cycle
+pub fn set_rate_adjust(emulator: &mut config::Emulator, adjust: f64) {
+    emulator.apu.resampler.set_rate_adjust(adjust);
+}
+
+fn clock_frame_counter(emulator: &mut config::Emulator) {
+    let apu = &mut emulator.apu;
+
+    if apu.frame_reset_delay > 0 {
+        apu.frame_reset_delay -= 1;
+        if apu.frame_reset_delay == 0 {
+            apu.frame_cycle = 0;
+            // switching to 5-step clocks the quarter and half frame units straight away
+            if apu.five_step_mode {
+                clock_quarter_frame(apu);
+                clock_half_frame(apu);
+            }
+            return;
+        }
+    }
+
+    apu.frame_cycle += 1;
+    let cycle = apu.frame_cycle;
+
+    if cycle == FRAME_STEP_1 || cycle == FRAME_STEP_3 {
+        clock_quarter_frame(apu);
+    } else if cycle == FRAME_STEP_2 {
+        clock_quarter_frame(apu);
+        clock_half_frame(apu);
+    }
+
+    if apu.five_step_mode {
+        if cycle == FRAME_STEP_5 {
+            clock_quarter_frame(apu);
+            clock_half_frame(apu);
+        } else if cycle == FRAME_STEP_5 + 1 {
+            apu.frame_cycle = 0;
+        }
+        return;
+    }
+
+    if cycle == FRAME_STEP_4 {
+        clock_quarter_frame(apu);
+        clock_half_frame(apu);
+    }
+
+    if (FRAME_STEP_4 - 1..=FRAME_STEP_4 + 1).contains(&cycle) && !apu.irq_inhibit {
+        apu.frame_irq_flag = true;
+    }
+
+    if cycle == FRAME_STEP_4 + 1 {
+        apu.frame_cycle = 0;
+    }
+}
+
+// envelopes and the triangle's linear counter
+fn clock_quarter_frame(apu: &mut APU) {
+    apu.pulse1.envelope.clock();
+    apu.pulse2.envelope.clock();
+    apu.noise.envelope.clock();
+    apu.triangle.clock_linear_counter();
+}
+
+// length counters and sweep units
+fn clock_half_frame(apu: &mut APU) {
+    apu.pulse1.length_counter.clock();
+    apu.pulse2.length_counter.clock();
+    apu.triangle.length_counter.clock();
+    apu.noise.length_counter.clock();
+    apu.pulse1.clock_sweep();
+    apu.pulse2.clock_sweep();
+}
+
+// the apu's contribution to the cpu irq line, it stays asserted until the flags are acknowledged
+pub fn irq_pending(emulator: &config::Emulator) -> bool {
+    return emulator.apu.frame_irq_flag || emulator.apu.dmc.irq_flag;
+}
+
+pub fn write_register(emulator: &mut config::Emulator, address: usize, value: u8) {
+    let apu = &mut emulator.apu;
+
+    match address {
+        0x4000..=0x4003 => apu.pulse1.write_register((address & 0x3) as u16, value),
+        0x4004..=0x4007 => apu.pulse2.write_register((address & 0x3) as u16, value),
+        0x4008..=0x400B => apu.triangle.write_register((address & 0x3) as u16, value),
+        0x400C..=0x400F => apu.noise.write_register((address & 0x3) as u16, value),
+        0x4010..=0x4013 => apu.dmc.write_register((address & 0x3) as u16, value),
+        0x4015 => {
+            // ---D NT21 channel enables
+            apu.pulse1.length_counter.set_enabled(value & 0x01 != 0);
+            apu.pulse2.length_counter.set_enabled(value & 0x02 != 0);
+            apu.triangle.length_counter.set_enabled(value & 0x04 != 0);
+            apu.noise.length_counter.set_enabled(value & 0x08 != 0);
+            apu.dmc.set_enabled(value & 0x10 != 0);
+        },
+        0x4017 => {
+            // MI-- ----, sequencer mode and irq inhibit
+            apu.five_step_mode = value & 0x80 != 0;
+            apu.irq_inhibit = value & 0x40 != 0;
+            if apu.irq_inhibit {
+                apu.frame_irq_flag = false;
+            }
+
+            // the reset lands 3 cycles after a write on an apu cycle, 4 if the write falls between them
+            apu.frame_reset_delay = if apu.cycle % 2 == 0 { 3 } else { 4 };
+        },
+        _ => {}
+    }
+}
+
+// $4015 read, IF-D NT21: dmc interrupt, frame interrupt, dmc active, length counter status
+pub fn read_status(emulator: &mut config::Emulator) -> u8 {
+    let apu = &emulator.apu;
+    let mut status: u8 = 0;
+
+    status |= apu.pulse1.length_counter.active() as u8;
+    status |= (apu.pulse2.length_counter.active() as u8) << 1;
+    status |= (apu.triangle.length_counter.active() as u8) << 2;
+    status |= (apu.noise.length_counter.active() as u8) << 3;
+    status |= ((apu.dmc.bytes_remaining > 0) as u8) << 4;
+    status |= (apu.frame_irq_flag as u8) << 6;
+    status |= (apu.dmc.irq_flag as u8) << 7;
+
+    // reading acknowledges the frame interrupt, but not the dmc one
+    emulator.apu.frame_irq_flag = false;
+
+    return status;
+}
+
+// power up state, all channels silenced
+pub fn reset(emulator: &mut config::Emulator) {
+    emulator.apu = APU::default();
+}
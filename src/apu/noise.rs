@@ -0,0 +1,79 @@
+use crate::apu::envelope;
+
+/*
+    noise channel, $400C-$400F, see https://www.nesdev.org/wiki/APU_Noise
+
+    $400C   --LC VVVV   length counter halt / envelope loop, constant volume, volume / envelope period
+    $400E   M--- PPPP   mode, period index
+    $400F   LLLL L---   length counter load
+*/
+
+// ntsc timer periods in cpu cycles
+static PERIOD_TABLE: [u16; 0x10] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+#[derive(Clone)]
+pub struct Noise {
+    pub mode: bool, // short mode, feedback from bit 6 instead of bit 1
+    pub shift_register: u16,
+    pub timer: u16,
+    pub timer_period: u16,
+    pub envelope: envelope::Envelope,
+    pub length_counter: envelope::LengthCounter,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise {
+            mode: false,
+            shift_register: 1, // loaded with 1 at power up
+            timer: 0,
+            timer_period: PERIOD_TABLE[0],
+            envelope: envelope::Envelope::default(),
+            length_counter: envelope::LengthCounter::default(),
+        }
+    }
+}
+
+impl Noise {
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.envelope.write_control(value);
+                self.length_counter.halt = value & 0x20 != 0;
+            },
+            2 => {
+                self.mode = value & 0x80 != 0;
+                self.timer_period = PERIOD_TABLE[(value & 0x0F) as usize];
+            },
+            3 => {
+                self.length_counter.load(value >> 3);
+                self.envelope.start = true;
+            },
+            _ => {}
+        }
+    }
+
+    // clocked every cpu cycle, the period table is in cpu cycles
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period - 1;
+
+        let tap = if self.mode { 6 } else { 1 };
+        let feedback = (self.shift_register & 0x1) ^ ((self.shift_register >> tap) & 0x1);
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.shift_register & 0x1 != 0 || !self.length_counter.active() {
+            return 0;
+        }
+
+        return self.envelope.output();
+    }
+}
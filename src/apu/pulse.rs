@@ -0,0 +1,120 @@
+use crate::apu::envelope;
+
+/*
+    pulse channels, $4000-$4003 and $4004-$4007, see https://www.nesdev.org/wiki/APU_Pulse
+
+    $4000   DDLC VVVV   duty, length counter halt / envelope loop, constant volume, volume / envelope period
+    $4001   EPPP NSSS   sweep enable, period, negate, shift
+    $4002   TTTT TTTT   timer low
+    $4003   LLLL LTTT   length counter load, timer high
+*/
+
+static DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+#[derive(Default, Clone)]
+pub struct Pulse {
+    pub ones_complement: bool, // pulse 1 negates with ones' complement, pulse 2 with twos' complement
+    pub duty: u8,
+    pub sequence: u8,
+    pub timer: u16,
+    pub timer_period: u16,
+    pub envelope: envelope::Envelope,
+    pub length_counter: envelope::LengthCounter,
+
+    pub sweep_enabled: bool,
+    pub sweep_period: u8,
+    pub sweep_negate: bool,
+    pub sweep_shift: u8,
+    pub sweep_divider: u8,
+    pub sweep_reload: bool,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Pulse {
+        Pulse {
+            ones_complement,
+            ..Default::default()
+        }
+    }
+
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.envelope.write_control(value);
+                self.length_counter.halt = value & 0x20 != 0;
+            },
+            1 => {
+                self.sweep_enabled = value & 0x80 != 0;
+                self.sweep_period = (value >> 4) & 0x07;
+                self.sweep_negate = value & 0x08 != 0;
+                self.sweep_shift = value & 0x07;
+                self.sweep_reload = true;
+            },
+            2 => {
+                self.timer_period = (self.timer_period & 0x0700) | value as u16;
+            },
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((value & 0x07) as u16) << 8);
+                self.length_counter.load(value >> 3);
+                // restarts the duty cycle and the envelope
+                self.sequence = 0;
+                self.envelope.start = true;
+            },
+            _ => {}
+        }
+    }
+
+    // clocked every apu cycle (every other cpu cycle)
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence = (self.sequence + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    // the sweep unit continuously computes its target, muting the channel if it would overflow even when disabled
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+
+        if self.sweep_negate {
+            let change = change + self.ones_complement as u16;
+            return self.timer_period.saturating_sub(change);
+        }
+
+        return self.timer_period + change;
+    }
+
+    fn muted(&self) -> bool {
+        return self.timer_period < 8 || self.sweep_target() > 0x7FF;
+    }
+
+    // clocked by the frame counter every half frame
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.muted() {
+            self.timer_period = self.sweep_target();
+        }
+
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if DUTY_TABLE[self.duty as usize][self.sequence as usize] == 0 || !self.length_counter.active() || self.muted() {
+            return 0;
+        }
+
+        return self.envelope.output();
+    }
+}
@@ -0,0 +1,134 @@
+use std::f64::consts::PI;
+
+/*
+    band limited resampler from the cpu clock rate down to the audio sample rate, in the style of blip_buf
+
+    rather than sampling the mixer output, every change in amplitude is added to the output buffer as a
+    band limited impulse (a windowed sinc, picked from a table of sub sample phases). Reading the samples
+    back out runs them through an integrator, which turns the impulses into band limited steps, followed
+    by a high pass to remove the dc offset like the console's own output filter does.
+
+    time is kept in 32.32 fixed point output samples, so the clock to sample ratio can be nudged by
+    dynamic rate control without any drift.
+*/
+
+static FRAC_BITS: u32 = 32;
+static PHASE_BITS: u32 = 5;
+static PHASES: usize = 1 << PHASE_BITS;
+static KERNEL_WIDTH: usize = 16;
+
+// cutoff as a fraction of the sample rate, a little under nyquist to leave room for the window roll off
+static CUTOFF: f64 = 0.45;
+// dc blocking high pass, roughly 37Hz at 44.1kHz
+static HIGH_PASS: f32 = 0.995;
+
+#[derive(Clone)]
+pub struct Resampler {
+    pub clock_rate: f64,
+    pub sample_rate: f64,
+    factor: u64, // output samples per clock in fixed point
+    offset: u64, // time of the next unread sample in fixed point
+    buffer: Vec<f32>,
+    kernel: Vec<f32>, // PHASES rows of KERNEL_WIDTH taps
+    integrator: f32,
+    high_pass_input: f32,
+    high_pass_output: f32,
+}
+
+impl Resampler {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Resampler {
+        let mut resampler = Resampler {
+            clock_rate,
+            sample_rate,
+            factor: 0,
+            offset: 0,
+            // enough for a tenth of a second of samples between reads
+            buffer: vec![0.0; (sample_rate / 10.0) as usize + KERNEL_WIDTH],
+            kernel: build_kernel(),
+            integrator: 0.0,
+            high_pass_input: 0.0,
+            high_pass_output: 0.0,
+        };
+
+        resampler.set_rate_adjust(1.0);
+        return resampler;
+    }
+
+    // scales the output rate, values above 1.0 produce more samples per clock
+    pub fn set_rate_adjust(&mut self, adjust: f64) {
+        self.factor = ((self.sample_rate * adjust / self.clock_rate) * (1u64 << FRAC_BITS) as f64) as u64;
+    }
+
+    // adds a change in amplitude at the given clock, relative to the start of the current frame
+    pub fn add_delta(&mut self, clock_time: u32, delta: f32) {
+        let time = self.offset + clock_time as u64 * self.factor;
+        let index = (time >> FRAC_BITS) as usize;
+        let phase = ((time >> (FRAC_BITS - PHASE_BITS)) as usize) & (PHASES - 1);
+
+        if index + KERNEL_WIDTH > self.buffer.len() {
+            return; // too far behind on reading, drop rather than overrun
+        }
+
+        let taps = &self.kernel[phase * KERNEL_WIDTH..(phase + 1) * KERNEL_WIDTH];
+        for (sample, tap) in self.buffer[index..index + KERNEL_WIDTH].iter_mut().zip(taps) {
+            *sample += delta * tap;
+        }
+    }
+
+    // moves time forward by the clocks run this frame, making the samples before it available
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as u64 * self.factor;
+    }
+
+    pub fn samples_available(&self) -> usize {
+        return ((self.offset >> FRAC_BITS) as usize).min(self.buffer.len() - KERNEL_WIDTH);
+    }
+
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        let count = self.samples_available();
+
+        for i in 0..count {
+            self.integrator += self.buffer[i];
+
+            let output = self.integrator - self.high_pass_input + HIGH_PASS * self.high_pass_output;
+            self.high_pass_input = self.integrator;
+            self.high_pass_output = output;
+
+            out.push(output);
+        }
+
+        // shift the unread part of the buffer, including the tails of impulses added past the end, down to the start
+        let len = self.buffer.len();
+        self.buffer.copy_within(count..len, 0);
+        self.buffer[len - count..].fill(0.0);
+        self.offset -= (count as u64) << FRAC_BITS;
+    }
+}
+
+// windowed sinc impulses, one row per sub sample phase, each normalised so a step keeps its full height
+fn build_kernel() -> Vec<f32> {
+    let mut kernel = vec![0.0; PHASES * KERNEL_WIDTH];
+
+    for phase in 0..PHASES {
+        let row = &mut kernel[phase * KERNEL_WIDTH..(phase + 1) * KERNEL_WIDTH];
+        let mut sum = 0.0;
+
+        for (i, tap) in row.iter_mut().enumerate() {
+            let x = i as f64 - (KERNEL_WIDTH / 2) as f64 + 1.0 - phase as f64 / PHASES as f64;
+            let sinc = if x == 0.0 { 1.0 } else { (2.0 * PI * CUTOFF * x).sin() / (2.0 * PI * CUTOFF * x) };
+
+            // blackman window over the kernel width
+            let n = (x + (KERNEL_WIDTH / 2) as f64) / KERNEL_WIDTH as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos();
+
+            *tap = (sinc * window) as f32;
+            sum += *tap;
+        }
+
+        for tap in row.iter_mut() {
+            *tap /= sum;
+        }
+    }
+
+    return kernel;
+}
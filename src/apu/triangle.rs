@@ -0,0 +1,79 @@
+use crate::apu::envelope;
+
+/*
+    triangle channel, $4008-$400B, see https://www.nesdev.org/wiki/APU_Triangle
+
+    $4008   CRRR RRRR   length counter halt / linear counter control, linear counter reload value
+    $400A   TTTT TTTT   timer low
+    $400B   LLLL LTTT   length counter load, timer high
+*/
+
+static SEQUENCE: [u8; 0x20] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+#[derive(Default, Clone)]
+pub struct Triangle {
+    pub sequence: u8,
+    pub timer: u16,
+    pub timer_period: u16,
+    pub length_counter: envelope::LengthCounter,
+
+    pub control: bool,
+    pub linear_counter: u8,
+    pub linear_counter_period: u8,
+    pub linear_counter_reload: bool,
+}
+
+impl Triangle {
+    pub fn write_register(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length_counter.halt = self.control;
+                self.linear_counter_period = value & 0x7F;
+            },
+            2 => {
+                self.timer_period = (self.timer_period & 0x0700) | value as u16;
+            },
+            3 => {
+                self.timer_period = (self.timer_period & 0x00FF) | (((value & 0x07) as u16) << 8);
+                self.length_counter.load(value >> 3);
+                self.linear_counter_reload = true;
+            },
+            _ => {}
+        }
+    }
+
+    // clocked every cpu cycle, the sequencer only moves while both counters are non zero
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.timer_period;
+        if self.linear_counter > 0 && self.length_counter.active() {
+            self.sequence = (self.sequence + 1) & 0x1F;
+        }
+    }
+
+    // clocked by the frame counter every quarter frame
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_period;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+
+        if !self.control {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    // the triangle isn't silenced when halted, it holds its current step
+    pub fn output(&self) -> u8 {
+        return SEQUENCE[self.sequence as usize];
+    }
+}
@@ -0,0 +1,67 @@
+use sdl2::audio::AudioQueue;
+use sdl2::audio::AudioSpecDesired;
+use crate::apu;
+use crate::config;
+/*
+    sdl audio output, the apu's resampled samples are pushed onto an sdl queue once per video frame.
+
+    the emulator is paced by the video loop, so the queue will slowly fill or drain as the two clocks
+    drift apart. Dynamic rate control (https://docs.libretro.com/development/cores/dynamic-rate-control/)
+    nudges the resampling ratio by a fraction of a percent based on how full the queue is, which keeps
+    it close to the target latency without audible pitch changes.
+*/
+
+static TARGET_LATENCY: f64 = 0.05; // seconds of audio to keep queued
+static MAX_RATE_DELTA: f64 = 0.005;
+static VOLUME: f32 = 0.5;
+
+pub struct Audio {
+    pub queue: AudioQueue<f32>,
+    pub samples: Vec<f32>,
+}
+
+pub fn init(sdl_context: &sdl2::Sdl) -> Result<Audio, String> {
+    let audio_subsystem = sdl_context.audio()?;
+
+    let desired = AudioSpecDesired {
+        freq: Some(apu::SAMPLE_RATE as i32),
+        channels: Some(1),
+        samples: Some(1024),
+    };
+
+    let queue = audio_subsystem.open_queue::<f32, _>(None, &desired)?;
+    queue.resume();
+
+    Ok(Audio {
+        queue,
+        samples: Vec::new(),
+    })
+}
+
+impl Audio {
+    // pulls this frame's samples out of the apu and queues them, then adjusts the rate for the next frame
+    pub fn queue_frame(&mut self, emulator: &mut config::Emulator) -> Result<(), String> {
+        self.samples.clear();
+        apu::end_frame(emulator, &mut self.samples);
+
+        for sample in self.samples.iter_mut() {
+            *sample *= VOLUME;
+        }
+        self.queue.queue_audio(&self.samples)?;
+
+        let target = apu::SAMPLE_RATE * TARGET_LATENCY;
+        let queued = (self.queue.size() as usize / std::mem::size_of::<f32>()) as f64;
+        let fill = (queued / (target * 2.0)).min(1.0);
+
+        // under half full produces slightly more samples per frame, over half full slightly fewer
+        apu::set_rate_adjust(emulator, 1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill));
+
+        Ok(())
+    }
+
+    // throws away this frame's samples, used when running faster than real time
+    pub fn skip_frame(&mut self, emulator: &mut config::Emulator) {
+        self.samples.clear();
+        apu::end_frame(emulator, &mut self.samples);
+    }
+}
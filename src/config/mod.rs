@@ -1,4 +1,6 @@
+use crate::apu;
 use crate::cpu;
+use crate::input;
 use crate::rom;
 use crate::ppu;
 use std::fmt;
@@ -8,6 +10,9 @@ pub struct Emulator {
     pub cpu: cpu::CPU,
     pub rom: rom::ROM,
     pub ppu: ppu::PPU,
+    pub apu: apu::APU,
+    pub input: input::Input,
+    pub mapper: Option<Box<dyn rom::mapper::Mapper>>,
     pub shut_down: bool,
 }
 
@@ -1,9 +1,12 @@
 mod register;
 mod instructions;
 use std::fmt;
+use crate::apu;
 use crate::config;
+use crate::input;
 use crate::ppu;
 use crate::ram;
+use crate::rom;
 use crate::ram::AddressingMode;
 /* 
     The NES cpu is a modified version of the 6502 processing unit, instructions
@@ -21,10 +24,12 @@ use crate::ram::AddressingMode;
     $4020-$FFFF	$BFE0	Cartridge space: PRG ROM, PRG RAM, and mapper registers
 */
 
+#[derive(Clone)]
 pub struct CPU {
     pub registers: register::Registers,
     pub memory: [u8; 0x10000],
     pub cycle: u32,
+    pub cycle_count: u64, // total cycles since power on, used for dma alignment
 }
 
 impl Default for CPU {
@@ -33,6 +38,7 @@ impl Default for CPU {
             registers: register::Registers {..Default::default()},
             memory: [0; 0x10000],
             cycle: 0,
+            cycle_count: 0,
         }
     }
 }
@@ -44,24 +50,35 @@ impl fmt::Display for CPU {
 }
 
 pub fn run_cycle(emulator: &mut config::Emulator) {
+    // interrupts are only polled between instructions
     if emulator.cpu.cycle == 0 {
-        run_next_instruction(emulator);
+        if emulator.ppu.nmi_pending {
+            emulator.ppu.nmi_pending = false;
+            nmi(emulator);
+        } else if irq_line(emulator) && !emulator.cpu.registers.status.contains(register::Status::I) {
+            irq(emulator);
+        } else {
+            run_next_instruction(emulator);
+        }
     }
 
     if emulator.cpu.cycle > 0 {
         emulator.cpu.cycle -= 1;
     }
+
+    emulator.cpu.cycle_count += 1;
+    rom::cpu_cycle(emulator);
 }
 
 // reads next byte in program, increments program counter
 pub fn read_program_byte(emulator: &mut config::Emulator) -> u8 {
-    let val = ram::read_u8(mapped_address, &mut emulator.cpu.memory, emulator.cpu.registers.pc.into());
+    let val = read_u8(emulator, mapped_address, emulator.cpu.registers.pc.into());
     emulator.cpu.registers.pc += 1;
     return val;
 }
 
 pub fn read_program_word(emulator: &mut config::Emulator) -> u16 {
-    let val = ram::read_u16(mapped_address, &mut emulator.cpu.memory, emulator.cpu.registers.pc.into());
+    let val = read_u16(emulator, emulator.cpu.registers.pc);
     emulator.cpu.registers.pc += 2;
     return val;
 }
@@ -80,7 +97,7 @@ pub fn mapped_address(addr: usize) -> usize {
 
 fn run_next_instruction(emulator: &mut config::Emulator) {
     // read next byte at the program counter location to get the opcode
-    let opcode = ram::read_u8(mapped_address, &mut emulator.cpu.memory, emulator.cpu.registers.pc.into());
+    let opcode = read_u8(emulator, mapped_address, emulator.cpu.registers.pc.into());
     emulator.cpu.registers.pc += 1;
 
     let mut opcode_iterator = instructions::OPCODES.iter();
@@ -146,7 +163,7 @@ fn write_stack_u16(emulator: &mut config::Emulator, value: u16) {
 }*/
 
 pub fn reset(emulator: &mut config::Emulator) {
-    emulator.cpu.registers.pc = ram::read_u16(mapped_address, &mut emulator.cpu.memory, 0xFFFC); // 0xFFFC is the reset vector, c000 for nestest
+    emulator.cpu.registers.pc = read_u16(emulator, 0xFFFC); // 0xFFFC is the reset vector, c000 for nestest
     emulator.cpu.registers.sp = 0xFD;
     emulator.cpu.registers.a = 0;
     emulator.cpu.registers.x = 0;
@@ -165,6 +182,11 @@ pub fn reset(emulator: &mut config::Emulator) {
     emulator.cpu.cycle += 8;
 }
 
+// irq is level triggered and shared by every source on the bus
+fn irq_line(emulator: &config::Emulator) -> bool {
+    return apu::irq_pending(emulator) || rom::irq_pending(emulator);
+}
+
 pub fn irq(emulator: &mut config::Emulator) {
     if !emulator.cpu.registers.status.contains(register::Status::I) {
         write_stack_u16(emulator, emulator.cpu.registers.pc);
@@ -174,7 +196,7 @@ pub fn irq(emulator: &mut config::Emulator) {
         emulator.cpu.registers.status.set(register::Status::I, true);
 
         write_stack_u8(emulator, emulator.cpu.registers.status.bits());
-        emulator.cpu.registers.pc = ram::read_u16(mapped_address, &mut emulator.cpu.memory, 0xFFFE);
+        emulator.cpu.registers.pc = read_u16(emulator, 0xFFFE);
 
         emulator.cpu.cycle += 7
     }
@@ -189,9 +211,9 @@ pub fn nmi(emulator: &mut config::Emulator) {
     emulator.cpu.registers.status.set(register::Status::I, true);
 
     write_stack_u8(emulator, emulator.cpu.registers.status.bits());
-    emulator.cpu.registers.pc = ram::read_u16(mapped_address, &mut emulator.cpu.memory, 0xFFFA);
+    emulator.cpu.registers.pc = read_u16(emulator, 0xFFFA);
 
-    emulator.cpu.cycle += 8
+    emulator.cpu.cycle += 7
 }
 
 fn handle_ppu_memory_read(emulator: &mut config::Emulator, address: usize) -> Option<u8> {
@@ -199,37 +221,30 @@ fn handle_ppu_memory_read(emulator: &mut config::Emulator, address: usize) -> Op
 
     match mapped_addr {
         0x2002 => {
-            // data = (status.reg & 0xE0) | (ppu_data_buffer & 0x1F); may not need for now
-            // Clear the vertical blanking flag
-            ppu::set_vblank(emulator, false);
-            // Reset Loopy's Address latch flag
-            emulator.ppu.ppu_addr_latch = false;
-            //return Some(data);
+            return Some(ppu::read_status(emulator));
+        },
+        0x2004 => {
+            return Some(ppu::read_oam_data(emulator));
         },
         0x2007 => {
             let mut data: u8;
+            let address = emulator.ppu.vram_addr & 0x3FFF;
             // Reads from the NameTable ram get delayed one cycle, 
             // so output buffer which contains the data from the 
             // previous read request
             data = emulator.ppu.ppu_data;
             // then update the buffer for next time
-            emulator.ppu.ppu_data = ram::read_u8(mapped_address, &mut emulator.ppu.memory, emulator.ppu.ppu_addr.into());
+            emulator.ppu.ppu_data = ppu::read_u8(emulator, address);
             // However, if the address was in the palette range, the
-            // data is not delayed, so it returns immediately
-            if emulator.ppu.ppu_addr >= 0x3F00 {
-                data = emulator.ppu.ppu_data
+            // data is not delayed, so it returns immediately, and the
+            // buffer is filled with the nametable byte underneath it
+            if address >= 0x3F00 {
+                data = emulator.ppu.ppu_data;
+                emulator.ppu.ppu_data = ppu::read_u8(emulator, address - 0x1000);
             }
             // All reads from PPU data automatically increment the nametable
             // address depending upon the mode set in the control register.
-            // If set to vertical mode, the increment is 32, so it skips
-            // one whole nametable row; in horizontal mode it just increments
-            // by 1, moving to the next column
-            let mut ppu_increment = 1;
-            if ppu::get_control_increment_mode(emulator) {
-                ppu_increment = 32; 
-            }
-
-            emulator.ppu.ppu_addr += ppu_increment;
+            ppu::increment_vram_addr(emulator);
             return Some(data);
         },
         _ => {
@@ -245,43 +260,119 @@ fn handle_ppu_memory_write(emulator: &mut config::Emulator, address: usize, data
     let mapped_addr = mapped_address(address);
     match mapped_addr {
         0x2000 => {
-            emulator.ppu.ppu_ctrl = data[0];
+            ppu::write_ctrl(emulator, data[0]);
+            // nametable select goes to t, so a mid frame write only takes effect at the next horizontal copy
+            emulator.ppu.temp_vram_addr = (emulator.ppu.temp_vram_addr & !0x0C00) | (((data[0] & 0x03) as u16) << 10);
         },
         0x2001 => {
             emulator.ppu.ppu_mask = data[0];
         },
+        0x2003 => {
+            emulator.ppu.oam_addr = data[0] as u16;
+        },
+        0x2004 => {
+            ppu::write_oam_data(emulator, data[0]);
+        },
+        0x2005 => {
+            if !emulator.ppu.write_latch {
+                // first write is x scroll, coarse x into t and the lower 3 bits into fine x
+                emulator.ppu.temp_vram_addr = (emulator.ppu.temp_vram_addr & !0x001F) | (data[0] >> 3) as u16;
+                emulator.ppu.fine_x = data[0] & 0x07;
+                emulator.ppu.write_latch = true;
+            } else {
+                // second write is y scroll, split into coarse y and fine y
+                emulator.ppu.temp_vram_addr = (emulator.ppu.temp_vram_addr & !0x73E0)
+                    | (((data[0] & 0x07) as u16) << 12)
+                    | (((data[0] >> 3) as u16) << 5);
+                emulator.ppu.write_latch = false;
+            }
+        },
         0x2006 => {
-            if emulator.ppu.ppu_addr_latch == false {
-                emulator.ppu.ppu_addr = (emulator.ppu.ppu_addr & 0xFF00) | data[0] as u16;
-                emulator.ppu.ppu_addr_latch = true;
+            if !emulator.ppu.write_latch {
+                // high byte first, only 6 bits fit and bit 14 of t is cleared
+                emulator.ppu.temp_vram_addr = (emulator.ppu.temp_vram_addr & 0x00FF) | (((data[0] & 0x3F) as u16) << 8);
+                emulator.ppu.write_latch = true;
             } else {
-                emulator.ppu.ppu_addr = (emulator.ppu.ppu_addr & 0x00FF) | ((data[0] as u16) << 8);
-                emulator.ppu.ppu_addr_latch = false;
+                emulator.ppu.temp_vram_addr = (emulator.ppu.temp_vram_addr & 0xFF00) | data[0] as u16;
+                emulator.ppu.vram_addr = emulator.ppu.temp_vram_addr;
+                emulator.ppu.write_latch = false;
             }
         },
         0x2007 => {
-            ram::write_block(ppu::mapped_address, &mut emulator.ppu.memory, emulator.ppu.ppu_addr.into(), data);
+            ppu::write_u8(emulator, emulator.ppu.vram_addr, data[0]);
             // All writes from PPU data automatically increment the nametable
             // address depending upon the mode set in the control register.
-            // If set to vertical mode, the increment is 32, so it skips
-            // one whole nametable row; in horizontal mode it just increments
-            // by 1, moving to the next column
-            let mut ppu_increment = 1;
-            if ppu::get_control_increment_mode(emulator) {
-                ppu_increment = 32; 
-            }
+            ppu::increment_vram_addr(emulator);
+        },
+        0x4014 => {
+            oam_dma(emulator, data[0]);
+        },
+        _ => {
+
+        }
+    }
+}
+
+// copies a 256 byte cpu page into oam, starting at oam_addr, see https://www.nesdev.org/wiki/PPU_registers#OAMDMA
+fn oam_dma(emulator: &mut config::Emulator, page: u8) {
+    let base = (page as usize) << 8;
+    for i in 0..0x100 {
+        let value = read_u8(emulator, mapped_address, base + i);
+        let oam_addr = emulator.ppu.oam_addr as usize;
+        emulator.ppu.oam[oam_addr] = value;
+        emulator.ppu.oam_addr = ((oam_addr + 1) & 0xFF) as u16;
+    }
+
+    // 1 dummy cycle, 256 read/write pairs, plus an alignment cycle if the dma starts on an odd cycle
+    emulator.cpu.cycle += 513 + (emulator.cpu.cycle_count & 0x1) as u32;
+}
 
-            emulator.ppu.ppu_addr +=  ppu_increment;
+
+fn handle_apu_memory_read(emulator: &mut config::Emulator, address: usize) -> Option<u8> {
+    match mapped_address(address) {
+        0x4015 => {
+            return Some(apu::read_status(emulator));
+        },
+        0x4016 => {
+            return Some(input::read_port(emulator, 0));
+        },
+        0x4017 => {
+            return Some(input::read_port(emulator, 1));
         },
         _ => {
 
         }
     }
+
+    return None;
 }
 
+fn handle_apu_memory_write(emulator: &mut config::Emulator, address: usize, data: &[u8]) {
+    let mapped_addr = mapped_address(address);
+    match mapped_addr {
+        0x4000..=0x4013 | 0x4015 | 0x4017 => {
+            apu::write_register(emulator, mapped_addr, data[0]);
+        },
+        0x4016 => {
+            input::write_strobe(emulator, data[0]);
+        },
+        _ => {
+
+        }
+    }
+}
 
 // interface for ram
 pub fn read_u8(emulator: &mut config::Emulator, addr_mapper: fn(usize)-> usize, address: usize ) -> u8 {
+    // cartridge space, unmapped reads see the high byte of the address left on the bus
+    if address >= 0x4020 {
+        return rom::cpu_read(emulator, address as u16).unwrap_or((address >> 8) as u8);
+    }
+
+    if let Some(x) = handle_apu_memory_read(emulator, address) {
+        return x;
+    }
+
     let ppu_res = handle_ppu_memory_read(emulator, address);
 
     match ppu_res {
@@ -294,6 +385,12 @@ pub fn read_u8(emulator: &mut config::Emulator, addr_mapper: fn(usize)-> usize,
     }
 }
 
+pub fn read_u16(emulator: &mut config::Emulator, address: u16) -> u16 {
+    let low = read_u8(emulator, mapped_address, address.into());
+    let high = read_u8(emulator, mapped_address, address.wrapping_add(1).into());
+    return low as u16 | ((high as u16) << 8);
+}
+
 // return value at address as well as a bool indicating if a page cross happened
 pub fn read_with_addressing_mode(emulator: &mut config::Emulator, addr_mapper: fn(usize)-> usize, addressing_mode: AddressingMode) -> (u8, bool) {
     let value: u8;
@@ -342,7 +439,17 @@ pub fn read_with_addressing_mode(emulator: &mut config::Emulator, addr_mapper: f
 }
 
 pub fn write_block(emulator: &mut config::Emulator, addr_mapper: fn(usize)-> usize, address: usize, data: &[u8]) {
+    if address >= 0x4020 {
+        rom::cpu_write(emulator, address as u16, data[0]);
+        return;
+    }
+
+    if (0x2000..0x4000).contains(&address) {
+        rom::ppu_register_write(emulator, mapped_address(address) as u16, data[0]);
+    }
+
     handle_ppu_memory_write(emulator, address, data);
+    handle_apu_memory_write(emulator, address, data);
     ram::write_block(addr_mapper, &mut emulator.cpu.memory, address, data);
 }
 
@@ -32,7 +32,7 @@ impl fmt::Display for Status {
 }
 
 // registers
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Registers {
     pub a: u8, // accumulator
     pub x: u8,
@@ -0,0 +1,287 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::Deserialize;
+use serde::Serialize;
+use sdl2::controller::Button;
+use sdl2::keyboard::Keycode;
+use crate::input::controller::Buttons;
+/*
+    input bindings, read from rusty_nes.toml next to the executable, a default file is written there on first run.
+
+    keys use sdl key names (https://wiki.libsdl.org/SDL2/SDL_Keycode) and controller buttons use sdl game
+    controller button names (a, b, x, y, back, start, dpup, dpdown, dpleft, dpright, leftshoulder, ...).
+    An empty string leaves the button unbound. Controllers are assigned to players in the order they are connected.
+    Players 3 and 4 are only read when a four player adapter is plugged in.
+
+    save_state keeps a snapshot of the running game in memory and load_state goes back to it.
+
+    power_pad is the 12 keys for power pad buttons 1-12, left to right then top to bottom on the mat.
+*/
+
+pub static FILE_NAME: &str = "rusty_nes.toml";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Hotkey {
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    FastForward,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ButtonNames {
+    pub a: String,
+    pub b: String,
+    pub select: String,
+    pub start: String,
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerBindings {
+    pub keyboard: ButtonNames,
+    pub controller: ButtonNames,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HotkeyNames {
+    pub pause: String,
+    pub reset: String,
+    #[serde(default = "default_save_state")]
+    pub save_state: String,
+    #[serde(default = "default_load_state")]
+    pub load_state: String,
+    pub fast_forward: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BindingsFile {
+    pub hotkeys: HotkeyNames,
+    pub player1: PlayerBindings,
+    pub player2: PlayerBindings,
+    #[serde(default = "default_extra_player")]
+    pub player3: PlayerBindings,
+    #[serde(default = "default_extra_player")]
+    pub player4: PlayerBindings,
+    #[serde(default = "default_power_pad")]
+    pub power_pad: Vec<String>,
+}
+
+impl Default for BindingsFile {
+    fn default() -> BindingsFile {
+        BindingsFile {
+            hotkeys: HotkeyNames {
+                pause: "P".to_string(),
+                reset: "R".to_string(),
+                save_state: default_save_state(),
+                load_state: default_load_state(),
+                fast_forward: "`".to_string(),
+            },
+            player1: PlayerBindings {
+                keyboard: ButtonNames {
+                    a: "X".to_string(),
+                    b: "Z".to_string(),
+                    select: "Right Shift".to_string(),
+                    start: "Return".to_string(),
+                    up: "Up".to_string(),
+                    down: "Down".to_string(),
+                    left: "Left".to_string(),
+                    right: "Right".to_string(),
+                },
+                controller: default_controller_names(),
+            },
+            player2: PlayerBindings {
+                keyboard: ButtonNames {
+                    a: "G".to_string(),
+                    b: "F".to_string(),
+                    select: "Tab".to_string(),
+                    start: "Space".to_string(),
+                    up: "W".to_string(),
+                    down: "S".to_string(),
+                    left: "A".to_string(),
+                    right: "D".to_string(),
+                },
+                controller: default_controller_names(),
+            },
+            player3: default_extra_player(),
+            player4: default_extra_player(),
+            power_pad: default_power_pad(),
+        }
+    }
+}
+
+// a 4x3 block of keys shaped like the mat
+fn default_save_state() -> String {
+    return "F5".to_string();
+}
+
+fn default_load_state() -> String {
+    return "F7".to_string();
+}
+
+fn default_power_pad() -> Vec<String> {
+    return ["1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F"].iter().map(|x| x.to_string()).collect();
+}
+
+// players 3 and 4 only get controllers by default, there isn't room left on the keyboard
+fn default_extra_player() -> PlayerBindings {
+    PlayerBindings {
+        keyboard: ButtonNames {
+            a: String::new(),
+            b: String::new(),
+            select: String::new(),
+            start: String::new(),
+            up: String::new(),
+            down: String::new(),
+            left: String::new(),
+            right: String::new(),
+        },
+        controller: default_controller_names(),
+    }
+}
+
+fn default_controller_names() -> ButtonNames {
+    ButtonNames {
+        a: "a".to_string(),
+        b: "x".to_string(),
+        select: "back".to_string(),
+        start: "start".to_string(),
+        up: "dpup".to_string(),
+        down: "dpdown".to_string(),
+        left: "dpleft".to_string(),
+        right: "dpright".to_string(),
+    }
+}
+
+impl ButtonNames {
+    fn pairs(&self) -> [(&String, Buttons); 8] {
+        return [
+            (&self.a, Buttons::A),
+            (&self.b, Buttons::B),
+            (&self.select, Buttons::SELECT),
+            (&self.start, Buttons::START),
+            (&self.up, Buttons::UP),
+            (&self.down, Buttons::DOWN),
+            (&self.left, Buttons::LEFT),
+            (&self.right, Buttons::RIGHT),
+        ];
+    }
+}
+
+// resolved lookup tables, (key or button, player, nes button)
+#[derive(Default)]
+pub struct Bindings {
+    pub keys: Vec<(Keycode, usize, Buttons)>,
+    pub buttons: Vec<(Button, usize, Buttons)>,
+    pub hotkeys: Vec<(Keycode, Hotkey)>,
+    pub power_pad: Vec<(Keycode, usize)>,
+}
+
+impl Bindings {
+    pub fn from_file(file: &BindingsFile) -> Bindings {
+        let mut bindings = Bindings::default();
+
+        for (player, player_bindings) in [&file.player1, &file.player2, &file.player3, &file.player4].iter().enumerate() {
+            for (name, button) in player_bindings.keyboard.pairs() {
+                if let Some(key) = parse_key(name) {
+                    bindings.keys.push((key, player, button));
+                }
+            }
+
+            for (name, button) in player_bindings.controller.pairs() {
+                if let Some(controller_button) = parse_button(name) {
+                    bindings.buttons.push((controller_button, player, button));
+                }
+            }
+        }
+
+        let hotkeys = [
+            (&file.hotkeys.pause, Hotkey::Pause),
+            (&file.hotkeys.reset, Hotkey::Reset),
+            (&file.hotkeys.save_state, Hotkey::SaveState),
+            (&file.hotkeys.load_state, Hotkey::LoadState),
+            (&file.hotkeys.fast_forward, Hotkey::FastForward),
+        ];
+        for (name, hotkey) in hotkeys {
+            if let Some(key) = parse_key(name) {
+                bindings.hotkeys.push((key, hotkey));
+            }
+        }
+
+        for (button, name) in file.power_pad.iter().take(12).enumerate() {
+            if let Some(key) = parse_key(name) {
+                bindings.power_pad.push((key, button));
+            }
+        }
+
+        return bindings;
+    }
+
+    pub fn find_key(&self, key: Keycode) -> Option<(usize, Buttons)> {
+        return self.keys.iter().find(|&x| x.0 == key).map(|x| (x.1, x.2));
+    }
+
+    pub fn find_button(&self, button: Button, player: usize) -> Option<Buttons> {
+        return self.buttons.iter().find(|&x| x.0 == button && x.1 == player).map(|x| x.2);
+    }
+
+    pub fn find_power_pad_key(&self, key: Keycode) -> Option<usize> {
+        return self.power_pad.iter().find(|&x| x.0 == key).map(|x| x.1);
+    }
+
+    pub fn find_hotkey(&self, key: Keycode) -> Option<Hotkey> {
+        return self.hotkeys.iter().find(|&x| x.0 == key).map(|x| x.1);
+    }
+}
+
+fn parse_key(name: &str) -> Option<Keycode> {
+    if name.is_empty() {
+        return None;
+    }
+
+    let key = Keycode::from_name(name);
+    if key.is_none() {
+        println!("unknown key \"{}\" in {}", name, FILE_NAME);
+    }
+    return key;
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    if name.is_empty() {
+        return None;
+    }
+
+    let button = Button::from_string(name);
+    if button.is_none() {
+        println!("unknown controller button \"{}\" in {}", name, FILE_NAME);
+    }
+    return button;
+}
+
+pub fn file_path() -> io::Result<PathBuf> {
+    return Ok(env::current_exe()?.with_file_name(FILE_NAME));
+}
+
+// reads the bindings file, writing out the defaults first if there isn't one yet
+pub fn load() -> io::Result<Bindings> {
+    let path = file_path()?;
+
+    if !path.exists() {
+        let contents = toml::to_string_pretty(&BindingsFile::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(&path, contents)?;
+        println!("wrote default bindings to {}", path.display());
+    }
+
+    let contents = fs::read_to_string(&path)?;
+    let file: BindingsFile = toml::from_str(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    return Ok(Bindings::from_file(&file));
+}
@@ -0,0 +1,54 @@
+use bitflags::bitflags;
+
+/*
+    standard controller, see https://www.nesdev.org/wiki/Standard_controller
+
+    while strobe is high the shift register is continuously reloaded with the button states, once it
+    goes low each read returns the next button in the order A, B, Select, Start, Up, Down, Left, Right.
+    After all 8 have been read an official controller returns 1s.
+*/
+
+bitflags! {
+    #[derive(Default)]
+    pub struct Buttons: u8 {
+        const A = 0b00000001;
+        const B = 0b00000010;
+        const SELECT = 0b00000100;
+        const START = 0b00001000;
+        const UP = 0b00010000;
+        const DOWN = 0b00100000;
+        const LEFT = 0b01000000;
+        const RIGHT = 0b10000000;
+    }
+}
+
+#[derive(Default)]
+pub struct Controller {
+    pub buttons: Buttons,
+    pub shift: u8,
+    pub strobe: bool,
+}
+
+impl Controller {
+    pub fn write_strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.shift = self.buttons.bits();
+        }
+    }
+
+    // returns the serial data bit in bit 0
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.buttons.bits() & 0x1;
+        }
+
+        let bit = self.shift & 0x1;
+        self.shift = (self.shift >> 1) | 0x80;
+        return bit;
+    }
+
+    pub fn set_button(&mut self, button: Buttons, pressed: bool) {
+        self.buttons.set(button, pressed);
+    }
+}
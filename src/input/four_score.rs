@@ -0,0 +1,91 @@
+use crate::input;
+use crate::input::controller;
+use crate::ppu;
+
+/*
+    four player adapters, see https://www.nesdev.org/wiki/Four_player_adapters
+
+    nes four score / satellite, each port shifts out 24 bits after a strobe:
+    reads 1-8       controller plugged into the port (1 on $4016, 2 on $4017)
+    reads 9-16      controller behind it (3 on $4016, 4 on $4017)
+    reads 17-24     signature, %00010000 on $4016 and %00100000 on $4017
+    after that      1s
+
+    famicom four players adapter (simple protocol), controllers 3 and 4 come in through the expansion port
+    and are read on D1 of $4016 and $4017 alongside controllers 1 and 2 on D0.
+*/
+
+static SIGNATURES: [u8; 2] = [0x10, 0x20];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+    Off,
+    FourScore,
+    Famicom,
+}
+
+impl Mode {
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "off" => Some(Mode::Off),
+            "nes" => Some(Mode::FourScore),
+            "famicom" => Some(Mode::Famicom),
+            _ => None,
+        }
+    }
+}
+
+pub struct FourScore {
+    pub mode: Mode,
+    pub controllers: [controller::Controller; 2], // players 3 and 4
+    pub reads: [u8; 2], // bits shifted out of each port since the last strobe
+    pub strobe: bool,
+}
+
+impl Default for FourScore {
+    fn default() -> FourScore {
+        FourScore {
+            mode: Mode::Off,
+            controllers: [controller::Controller::default(), controller::Controller::default()],
+            reads: [0; 2],
+            strobe: false,
+        }
+    }
+}
+
+impl FourScore {
+    pub fn write_strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.reads = [0; 2];
+        }
+
+        for controller in self.controllers.iter_mut() {
+            controller.write_strobe(strobe);
+        }
+    }
+
+    // 24 bit serial read for the nes four score, first is whatever is plugged into the port itself
+    pub fn read(&mut self, port: usize, first: &mut input::Device, ppu: &ppu::PPU) -> u8 {
+        let n = self.reads[port];
+        if !self.strobe && n < 24 {
+            self.reads[port] += 1;
+        }
+
+        match n {
+            0..=7 => first.read(port, false, ppu) & 0x1,
+            8..=15 => self.controllers[port].read(),
+            16..=23 => (SIGNATURES[port] >> (23 - n)) & 0x1,
+            _ => 1,
+        }
+    }
+
+    // D1 bits from the famicom adapter, nothing when it's not plugged in
+    pub fn read_expansion(&mut self, port: usize) -> u8 {
+        if self.mode != Mode::Famicom {
+            return 0;
+        }
+
+        return self.controllers[port].read() << 1;
+    }
+}
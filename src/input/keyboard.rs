@@ -0,0 +1,81 @@
+use sdl2::keyboard::Keycode;
+
+/*
+    family basic keyboard, famicom expansion port only, see https://www.nesdev.org/wiki/Family_BASIC_Keyboard
+
+    $4016 write
+    -----CBA
+         ||+- reset to row 0
+         |+-- column select, going from 1 to 0 moves on to the next row
+         +--- enable keyboard
+
+    $4017 read D1-D4 are 4 keys of the selected row and column, 0 when pressed. 9 rows of 2 columns of 4 keys
+*/
+
+static ROWS: usize = 9;
+
+// where each famicom key ended up on a pc keyboard, [row][column][D1-D4]
+static MATRIX: [[[Keycode; 4]; 2]; 9] = [
+    [[Keycode::RightBracket, Keycode::LeftBracket, Keycode::Return, Keycode::F8], [Keycode::End, Keycode::Backslash, Keycode::RShift, Keycode::RAlt]], // ] [ RETURN F8, STOP YEN RSHIFT KANA
+    [[Keycode::Semicolon, Keycode::Quote, Keycode::Backquote, Keycode::F7], [Keycode::Equals, Keycode::Minus, Keycode::Slash, Keycode::RCtrl]], // ; : @ F7, ^ - / _
+    [[Keycode::K, Keycode::L, Keycode::O, Keycode::F6], [Keycode::Num0, Keycode::P, Keycode::Comma, Keycode::Period]],
+    [[Keycode::J, Keycode::U, Keycode::I, Keycode::F5], [Keycode::Num8, Keycode::Num9, Keycode::N, Keycode::M]],
+    [[Keycode::H, Keycode::G, Keycode::Y, Keycode::F4], [Keycode::Num6, Keycode::Num7, Keycode::V, Keycode::B]],
+    [[Keycode::D, Keycode::R, Keycode::T, Keycode::F3], [Keycode::Num4, Keycode::Num5, Keycode::C, Keycode::F]],
+    [[Keycode::A, Keycode::S, Keycode::W, Keycode::F2], [Keycode::Num3, Keycode::E, Keycode::Z, Keycode::X]],
+    [[Keycode::LCtrl, Keycode::Q, Keycode::Tab, Keycode::F1], [Keycode::Num2, Keycode::Num1, Keycode::LAlt, Keycode::LShift]], // CTR Q ESC F1, 2 1 GRPH LSHIFT
+    [[Keycode::Left, Keycode::Right, Keycode::Up, Keycode::Home], [Keycode::Insert, Keycode::Backspace, Keycode::Space, Keycode::Down]], // CLR HOME, INS DEL SPACE
+];
+
+#[derive(Default)]
+pub struct Keyboard {
+    pub keys: [[u8; 2]; 9], // pressed keys of each row and column, bit 0 is D1
+    pub row: usize,
+    pub column: usize,
+    pub enabled: bool,
+}
+
+impl Keyboard {
+    // returns false if the key isn't on the famicom keyboard
+    pub fn set_key(&mut self, key: Keycode, pressed: bool) -> bool {
+        for (row, columns) in MATRIX.iter().enumerate() {
+            for (column, keys) in columns.iter().enumerate() {
+                if let Some(bit) = keys.iter().position(|&x| x == key) {
+                    if pressed {
+                        self.keys[row][column] |= 1 << bit;
+                    } else {
+                        self.keys[row][column] &= !(1 << bit);
+                    }
+                    return true;
+                }
+            }
+        }
+
+        return false;
+    }
+
+    pub fn write(&mut self, out: u8) {
+        let column = ((out >> 1) & 0x1) as usize;
+        self.enabled = out & 0x4 != 0;
+
+        if out & 0x1 != 0 {
+            self.row = 0;
+        } else if self.column == 1 && column == 0 {
+            self.row = (self.row + 1).min(ROWS);
+        }
+        self.column = column;
+    }
+
+    pub fn read(&self, register: usize) -> u8 {
+        if register != 1 || !self.enabled {
+            return 0;
+        }
+
+        // past the last row nothing is pressed
+        if self.row >= ROWS {
+            return 0x1E;
+        }
+
+        return (!self.keys[self.row][self.column] & 0x0F) << 1;
+    }
+}
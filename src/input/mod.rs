@@ -0,0 +1,192 @@
+pub mod controller;
+pub mod bindings;
+pub mod zapper;
+pub mod four_score;
+pub mod vaus;
+pub mod power_pad;
+pub mod keyboard;
+use sdl2::keyboard::Keycode;
+use crate::config;
+use crate::ppu;
+/*
+    input ports, see https://www.nesdev.org/wiki/Input_devices
+
+    $4016 write     OUT0-2, OUT0 is the strobe for both ports, the famicom expansion port sees all 3
+    $4016 read      port 1 data, expansion port D1
+    $4017 read      port 2 data, expansion port D1-D4
+
+    only the low bits of a read are driven by the devices, the rest are open bus which is
+    almost always the high byte of the address, $40
+*/
+
+pub static OPEN_BUS: u8 = 0x40;
+
+// what is plugged into a port or the famicom expansion port
+pub enum Device {
+    None,
+    Controller(controller::Controller),
+    Zapper(zapper::Zapper),
+    Vaus(vaus::Vaus),
+    PowerPad(power_pad::PowerPad),
+    Keyboard(keyboard::Keyboard),
+}
+
+impl Device {
+    pub fn from_name(name: &str) -> Option<Device> {
+        match name {
+            "none" => Some(Device::None),
+            "controller" => Some(Device::Controller(controller::Controller::default())),
+            "zapper" => Some(Device::Zapper(zapper::Zapper::default())),
+            "vaus" => Some(Device::Vaus(vaus::Vaus::default())),
+            "powerpad" => Some(Device::PowerPad(power_pad::PowerPad::default())),
+            "keyboard" => Some(Device::Keyboard(keyboard::Keyboard::default())),
+            _ => None,
+        }
+    }
+
+    pub fn write(&mut self, out: u8) {
+        match self {
+            Device::Controller(controller) => controller.write_strobe(out & 0x1 != 0),
+            Device::Vaus(vaus) => vaus.write_strobe(out & 0x1 != 0),
+            Device::PowerPad(power_pad) => power_pad.write(out),
+            Device::Keyboard(keyboard) => keyboard.write(out),
+            _ => {}
+        }
+    }
+
+    // returns the data bits for register 0 ($4016) or 1 ($4017), D0-D4
+    pub fn read(&mut self, register: usize, expansion: bool, ppu: &ppu::PPU) -> u8 {
+        match self {
+            Device::None => 0,
+            // an expansion port controller is read on D1 of $4016
+            Device::Controller(controller) if expansion => if register == 0 { controller.read() << 1 } else { 0 },
+            Device::Controller(controller) => controller.read(),
+            // the famicom zapper uses the same bits, but only on $4017
+            Device::Zapper(zapper) => if register == 1 || !expansion { zapper.read(ppu) } else { 0 },
+            Device::Vaus(vaus) => vaus.read(register, expansion),
+            Device::PowerPad(power_pad) => power_pad.read(register, expansion),
+            // the keyboard only exists as an expansion device
+            Device::Keyboard(keyboard) => if expansion { keyboard.read(register) } else { 0 },
+        }
+    }
+}
+
+pub struct Input {
+    pub ports: [Device; 2],
+    pub expansion: Device,
+    pub four_score: four_score::FourScore,
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input {
+            ports: [
+                Device::Controller(controller::Controller::default()),
+                Device::Controller(controller::Controller::default()),
+            ],
+            expansion: Device::None,
+            four_score: four_score::FourScore::default(),
+        }
+    }
+}
+
+impl Input {
+    fn devices_mut(&mut self) -> impl Iterator<Item = &mut Device> {
+        return self.ports.iter_mut().chain(std::iter::once(&mut self.expansion));
+    }
+}
+
+pub fn write_strobe(emulator: &mut config::Emulator, value: u8) {
+    for device in emulator.input.devices_mut() {
+        device.write(value & 0x7);
+    }
+    emulator.input.four_score.write_strobe(value & 0x1 != 0);
+}
+
+pub fn read_port(emulator: &mut config::Emulator, port: usize) -> u8 {
+    let input = &mut emulator.input;
+    let expansion = input.expansion.read(port, true, &emulator.ppu) | input.four_score.read_expansion(port);
+
+    if input.four_score.mode == four_score::Mode::FourScore {
+        return OPEN_BUS | expansion | input.four_score.read(port, &mut input.ports[port], &emulator.ppu);
+    }
+
+    return OPEN_BUS | expansion | input.ports[port].read(port, false, &emulator.ppu);
+}
+
+// picks the devices the rom asks for in its nes 2.0 header, see https://www.nesdev.org/wiki/NES_2.0#Default_Expansion_Device
+pub fn init_devices(emulator: &mut config::Emulator) {
+    if !emulator.rom.header.nes_2 {
+        return;
+    }
+
+    match emulator.rom.header.expansion_device {
+        0x02 => emulator.input.four_score.mode = four_score::Mode::FourScore,
+        0x03 => emulator.input.four_score.mode = four_score::Mode::Famicom,
+        0x08 => emulator.input.ports[1] = Device::Zapper(zapper::Zapper::default()),
+        0x09 => {
+            emulator.input.ports[0] = Device::Zapper(zapper::Zapper::default());
+            emulator.input.ports[1] = Device::Zapper(zapper::Zapper::default());
+        },
+        0x0B | 0x0C => emulator.input.ports[1] = Device::PowerPad(power_pad::PowerPad::default()),
+        0x0D | 0x0E => emulator.input.expansion = Device::PowerPad(power_pad::PowerPad::default()),
+        0x0F => emulator.input.ports[1] = Device::Vaus(vaus::Vaus::default()),
+        0x10 => emulator.input.expansion = Device::Vaus(vaus::Vaus::default()),
+        0x23 => emulator.input.expansion = Device::Keyboard(keyboard::Keyboard::default()),
+        _ => {}
+    }
+}
+
+// controller buttons for a player, ignored if that player's port doesn't have a controller in it.
+// players 3 and 4 are on the four player adapter
+pub fn set_button(emulator: &mut config::Emulator, player: usize, button: controller::Buttons, pressed: bool) {
+    match player {
+        0 | 1 => {
+            if let Device::Controller(controller) = &mut emulator.input.ports[player] {
+                controller.set_button(button, pressed);
+            }
+        },
+        2 | 3 => emulator.input.four_score.controllers[player - 2].set_button(button, pressed),
+        _ => {}
+    }
+}
+
+// mouse position in ppu pixels, aims zappers and turns vaus knobs. Off screen is outside 256x240
+pub fn set_mouse_position(emulator: &mut config::Emulator, x: i32, y: i32) {
+    for device in emulator.input.devices_mut() {
+        match device {
+            Device::Zapper(zapper) => zapper.aim(x, y),
+            Device::Vaus(vaus) => vaus.set_x(x),
+            _ => {}
+        }
+    }
+}
+
+// the left mouse button pulls zapper triggers and presses the vaus fire button
+pub fn set_mouse_button(emulator: &mut config::Emulator, pressed: bool) {
+    for device in emulator.input.devices_mut() {
+        match device {
+            Device::Zapper(zapper) => zapper.trigger = pressed,
+            Device::Vaus(vaus) => vaus.fire = pressed,
+            _ => {}
+        }
+    }
+}
+
+// button is 0-11, pressed on every power pad plugged in
+pub fn set_power_pad_button(emulator: &mut config::Emulator, button: usize, pressed: bool) {
+    for device in emulator.input.devices_mut() {
+        if let Device::PowerPad(power_pad) = device {
+            power_pad.set_button(button, pressed);
+        }
+    }
+}
+
+// returns false if there's no keyboard or the key isn't on it
+pub fn set_keyboard_key(emulator: &mut config::Emulator, key: Keycode, pressed: bool) -> bool {
+    if let Device::Keyboard(keyboard) = &mut emulator.input.expansion {
+        return keyboard.set_key(key, pressed);
+    }
+
+    return false;
+}
@@ -0,0 +1,83 @@
+/*
+    power pad and family trainer mat, see https://www.nesdev.org/wiki/Power_Pad
+
+    buttons are numbered 1-12 across the mat, left to right then top to bottom
+
+     1  2  3  4
+     5  6  7  8
+     9 10 11 12
+
+    nes, either port, two serial streams latched on strobe:
+    D3      2, 1, 5, 9, 6, 10, 11, 7
+    D4      4, 3, 12, 8, then 1s
+
+    famicom expansion (family trainer), OUT0-2 pick which rows are scanned, each active low:
+    OUT2 0  buttons 1-4
+    OUT1 0  buttons 5-8
+    OUT0 0  buttons 9-12
+    $4017 D1-D4 return the 4 columns, 0 when pressed
+*/
+
+static D3_ORDER: [usize; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+static D4_ORDER: [usize; 4] = [4, 3, 12, 8];
+
+#[derive(Default)]
+pub struct PowerPad {
+    pub buttons: [bool; 12],
+    pub shift_d3: u8,
+    pub shift_d4: u8,
+    pub strobe: bool,
+    pub out: u8,
+}
+
+impl PowerPad {
+    // button is 0-11 for buttons 1-12
+    pub fn set_button(&mut self, button: usize, pressed: bool) {
+        self.buttons[button] = pressed;
+    }
+
+    pub fn write(&mut self, out: u8) {
+        self.out = out;
+        self.strobe = out & 0x1 != 0;
+
+        if self.strobe {
+            self.shift_d3 = 0;
+            for (i, &button) in D3_ORDER.iter().enumerate() {
+                self.shift_d3 |= (self.buttons[button - 1] as u8) << i;
+            }
+
+            self.shift_d4 = 0xF0;
+            for (i, &button) in D4_ORDER.iter().enumerate() {
+                self.shift_d4 |= (self.buttons[button - 1] as u8) << i;
+            }
+        }
+    }
+
+    pub fn read(&mut self, register: usize, expansion: bool) -> u8 {
+        if expansion {
+            return if register == 1 { self.read_rows() } else { 0 };
+        }
+
+        let value = ((self.shift_d3 & 0x1) << 3) | ((self.shift_d4 & 0x1) << 4);
+        if !self.strobe {
+            self.shift_d3 = (self.shift_d3 >> 1) | 0x80;
+            self.shift_d4 = (self.shift_d4 >> 1) | 0x80;
+        }
+        return value;
+    }
+
+    fn read_rows(&self) -> u8 {
+        let mut pressed = 0;
+        for row in 0..3 {
+            if self.out & (0x4 >> row) != 0 {
+                continue;
+            }
+
+            for column in 0..4 {
+                pressed |= (self.buttons[row * 4 + column] as u8) << column;
+            }
+        }
+
+        return (!pressed & 0x0F) << 1;
+    }
+}
@@ -0,0 +1,70 @@
+/*
+    arkanoid vaus controller, see https://www.nesdev.org/wiki/Arkanoid_controller
+
+    the knob position is latched on strobe and shifted out inverted, most significant bit first
+
+    nes, either port        D3 fire button, D4 knob data
+    famicom expansion       $4016 D1 fire button, $4017 D1 knob data
+*/
+
+// range of knob values the games expect, from all the way left to all the way right
+static MIN_POSITION: i32 = 0x62;
+static MAX_POSITION: i32 = 0xF2;
+
+pub struct Vaus {
+    pub position: u8,
+    pub fire: bool,
+    pub shift: u8,
+    pub strobe: bool,
+}
+
+impl Default for Vaus {
+    fn default() -> Vaus {
+        Vaus {
+            position: MIN_POSITION as u8,
+            fire: false,
+            shift: 0,
+            strobe: false,
+        }
+    }
+}
+
+impl Vaus {
+    // x is in ppu pixels, so the width of the screen covers the whole range of the knob. The knob stays
+    // where it was when the mouse leaves the screen
+    pub fn set_x(&mut self, x: i32) {
+        if !(0..=255).contains(&x) {
+            return;
+        }
+
+        self.position = (MIN_POSITION + x * (MAX_POSITION - MIN_POSITION) / 255) as u8;
+    }
+
+    pub fn write_strobe(&mut self, strobe: bool) {
+        self.strobe = strobe;
+        if strobe {
+            self.shift = self.position;
+        }
+    }
+
+    fn read_bit(&mut self) -> u8 {
+        let bit = (!self.shift >> 7) & 0x1;
+        if !self.strobe {
+            self.shift <<= 1;
+        }
+        return bit;
+    }
+
+    pub fn read(&mut self, register: usize, expansion: bool) -> u8 {
+        let fire = self.fire as u8;
+
+        if !expansion {
+            return (fire << 3) | (self.read_bit() << 4);
+        }
+
+        if register == 0 {
+            return fire << 1;
+        }
+        return self.read_bit() << 1;
+    }
+}
@@ -0,0 +1,73 @@
+use crate::ppu;
+
+/*
+    NES zapper light gun, see https://www.nesdev.org/wiki/Zapper
+
+    read bits
+    ---4 3---
+       | +---- light sense (0: light detected)
+       +------ trigger (1: pulled)
+
+    the photodiode stays lit for a while after the beam passes, so light is sensed if a bright pixel
+    near the aim point was drawn within the last few scanlines of the current frame.
+*/
+
+static SENSE_RADIUS: i32 = 2;
+static SENSE_SCANLINES: i32 = 20;
+static BRIGHTNESS_THRESHOLD: u32 = 180;
+
+#[derive(Default)]
+pub struct Zapper {
+    pub x: i32, // aim point in ppu pixels, off screen when outside 256x240
+    pub y: i32,
+    pub trigger: bool,
+}
+
+impl Zapper {
+    pub fn aim(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn read(&self, ppu: &ppu::PPU) -> u8 {
+        let light = if self.senses_light(ppu) { 0 } else { 0x08 };
+        let trigger = if self.trigger { 0x10 } else { 0 };
+        return light | trigger;
+    }
+
+    fn senses_light(&self, ppu: &ppu::PPU) -> bool {
+        let width = ppu::SCREEN_WIDTH as i32;
+        let height = ppu::SCREEN_HEIGHT as i32;
+        let scanline = ppu.scanline as i32;
+        let dot = ppu.cycle as i32 - 1;
+
+        if self.x < 0 || self.x >= width || self.y < 0 || self.y >= height {
+            return false;
+        }
+
+        for y in (self.y - SENSE_RADIUS).max(0)..=(self.y + SENSE_RADIUS).min(height - 1) {
+            // only pixels the beam has drawn recently this frame can light the sensor
+            if y > scanline || scanline - y > SENSE_SCANLINES {
+                continue;
+            }
+
+            for x in (self.x - SENSE_RADIUS).max(0)..=(self.x + SENSE_RADIUS).min(width - 1) {
+                if y == scanline && x >= dot {
+                    continue;
+                }
+
+                if brightness(ppu, x, y) >= BRIGHTNESS_THRESHOLD {
+                    return true;
+                }
+            }
+        }
+
+        return false;
+    }
+}
+
+fn brightness(ppu: &ppu::PPU, x: i32, y: i32) -> u32 {
+    let pixel = ppu.frame_buffer[(y * ppu::SCREEN_WIDTH as i32 + x) as usize];
+    let (r, g, b) = ppu.palette.colour((pixel & 0x3F) as u8, (pixel >> 6) as u8);
+    return (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+}
@@ -1,40 +1,159 @@
+mod apu;
+mod audio;
 mod config;
 mod cpu;
+mod input;
 mod ram;
 mod rom;
 mod ppu;
+mod state;
 use std::env;
 use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::event::Event;
+use sdl2::event::WindowEvent;
 use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::controller::GameController;
 use std::time::Duration;
+use std::time::Instant;
 
+// frames run per displayed frame while fast forward is held
+static FAST_FORWARD_FRAMES: u32 = 4;
+
+// usage: rusty_nes <rom file> [--palette <.pal file>] [--port1 <device>] [--port2 <device>] [--expansion <device>] [--four-player <mode>] [--mmc3 <revision>]
+// devices: controller, zapper, vaus, powerpad, keyboard (expansion only), none
+// four player modes: nes (four score), famicom, off
+// mmc3 revisions: sharp, nec, mmc6
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let rom_file_name = (&args[1]).to_string();
-    boot(rom_file_name);
+    let mut rom_file_name: Option<String> = None;
+    let mut palette_file_name: Option<String> = None;
+    let mut port_devices: [Option<String>; 3] = [None, None, None];
+    let mut four_player: Option<String> = None;
+    let mut mmc3_revision: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--palette" => {
+                i += 1;
+                palette_file_name = args.get(i).cloned();
+            },
+            "--port1" | "--port2" => {
+                let port = if args[i] == "--port1" { 0 } else { 1 };
+                i += 1;
+                port_devices[port] = args.get(i).cloned();
+            },
+            "--expansion" => {
+                i += 1;
+                port_devices[2] = args.get(i).cloned();
+            },
+            "--four-player" => {
+                i += 1;
+                four_player = args.get(i).cloned();
+            },
+            "--mmc3" => {
+                i += 1;
+                mmc3_revision = args.get(i).cloned();
+            },
+            _ => rom_file_name = Some(args[i].to_string()),
+        }
+        i += 1;
+    }
+
+    boot(rom_file_name.expect("no rom file given"), palette_file_name, port_devices, four_player, mmc3_revision);
 }
 
-fn boot(file_name: String) {
+fn boot(file_name: String, palette_file_name: Option<String>, port_devices: [Option<String>; 3], four_player: Option<String>, mmc3_revision: Option<String>) {
     let mut emulator = config::Emulator::default();
     match emulator.rom.load_file(&file_name) {
         Ok(()) => println!("{} loaded", file_name),
         Err(error) => panic!("Problem opening the file: {:?}", error),
     };
 
-    rom::init_mapper(&mut emulator);
-    cpu::reset(&mut emulator);
-    ppu::reset(&mut emulator);
+    // the rom picks its devices first, the command line overrides them
+    input::init_devices(&mut emulator);
+    for (port, name) in port_devices.iter().enumerate() {
+        if let Some(name) = name {
+            let device = match input::Device::from_name(name) {
+                Some(device) => device,
+                None => panic!("Unknown device: {}, expected controller, zapper, vaus, powerpad, keyboard or none", name),
+            };
+
+            // the third slot is the famicom expansion port
+            if port < 2 {
+                emulator.input.ports[port] = device;
+            } else {
+                emulator.input.expansion = device;
+            }
+        }
+    }
+
+    if let Some(name) = four_player {
+        match input::four_score::Mode::from_name(&name) {
+            Some(mode) => emulator.input.four_score.mode = mode,
+            None => panic!("Unknown four player mode: {}, expected nes, famicom or off", name),
+        }
+    }
+
+    if let Some(palette_file_name) = palette_file_name {
+        match emulator.ppu.palette.load_file(&palette_file_name) {
+            Ok(()) => println!("{} loaded", palette_file_name),
+            Err(error) => panic!("Problem opening the palette: {:?}", error),
+        };
+    }
+
+    if let Some(name) = mmc3_revision {
+        match rom::mapper::mmc3::Revision::from_name(&name) {
+            Some(revision) => emulator.rom.mmc3_revision = Some(revision),
+            None => panic!("Unknown mmc3 revision: {}, expected sharp, nec or mmc6", name),
+        }
+    }
+
+    match rom::init_mapper(&emulator.rom) {
+        Ok(mapper) => emulator.mapper = Some(mapper),
+        Err(error) => panic!("Problem loading the mapper: {}", error),
+    };
+
+    if let Err(error) = rom::load_save_ram(&mut emulator, &file_name) {
+        println!("Problem loading save ram: {:?}", error);
+    }
+
+    reset(&mut emulator);
     println!("{}", emulator);
 
     init_canvas(&mut emulator).expect("initialization failed");
+
+    if let Err(error) = rom::write_save_ram(&emulator, &file_name) {
+        println!("Problem writing save ram: {:?}", error);
+    }
+}
+
+fn reset(emulator: &mut config::Emulator) {
+    cpu::reset(emulator);
+    ppu::reset(emulator);
+    apu::reset(emulator);
+}
+
+// runs the emulator until the ppu finishes the next frame
+fn run_frame(emulator: &mut config::Emulator) {
+    while !emulator.ppu.frame_complete {
+        ppu::run_cycle(emulator);
+        ppu::run_cycle(emulator);
+        ppu::run_cycle(emulator);
+
+        cpu::run_cycle(emulator);
+        apu::run_cycle(emulator);
+    }
+    emulator.ppu.frame_complete = false;
 }
 
 fn init_canvas(emulator: &mut config::Emulator) -> Result<(), String> {
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
-    let window = video_subsystem.window("rustyNES", 256, 240)
+    let window = video_subsystem.window("rustyNES", ppu::SCREEN_WIDTH, ppu::SCREEN_HEIGHT)
         .position_centered()
         .build()
         .expect("could not initialize video subsystem");
@@ -42,13 +161,36 @@ fn init_canvas(emulator: &mut config::Emulator) -> Result<(), String> {
     let mut canvas = window.into_canvas().build()
         .expect("could not make a canvas");
 
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, ppu::SCREEN_WIDTH, ppu::SCREEN_HEIGHT)
+        .map_err(|e| e.to_string())?;
+    let mut frame = vec![0; (ppu::SCREEN_WIDTH * ppu::SCREEN_HEIGHT * 3) as usize];
+
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
+    let mut audio = audio::init(&sdl_context)?;
+    let frame_duration = Duration::new(0, 1_000_000_000u32 / 60);
+
+    let bindings = match input::bindings::load() {
+        Ok(bindings) => bindings,
+        Err(error) => {
+            println!("Problem loading {}, using default bindings: {:?}", input::bindings::FILE_NAME, error);
+            input::bindings::Bindings::from_file(&input::bindings::BindingsFile::default())
+        },
+    };
+
+    // controllers are assigned to players in the order they are opened
+    let controller_subsystem = sdl_context.game_controller()?;
+    let mut controllers: Vec<GameController> = Vec::new();
+
+    let mut paused = false;
+    let mut fast_forward = false;
+    let mut save_state: Option<state::SaveState> = None;
+
     let mut event_pump = sdl_context.event_pump()?;
-    //let mut cycle = 0;
     'running: loop {
-        //canvas.set_draw_color(Color::RGB(cycle, 64, 255 - i));
+        let frame_start = Instant::now();
         canvas.clear();
         for event in event_pump.poll_iter() {
             match event {
@@ -56,20 +198,114 @@ fn init_canvas(emulator: &mut config::Emulator) -> Result<(), String> {
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running;
                 },
+                // a famicom keyboard takes every key it has, so typing doesn't trigger anything else
+                Event::KeyDown { keycode: Some(key), .. } if input::set_keyboard_key(emulator, key, true) => {},
+                Event::KeyUp { keycode: Some(key), .. } if input::set_keyboard_key(emulator, key, false) => {},
+                Event::KeyDown { keycode: Some(key), repeat, .. } => {
+                    if let Some((player, button)) = bindings.find_key(key) {
+                        input::set_button(emulator, player, button, true);
+                    }
+
+                    if let Some(button) = bindings.find_power_pad_key(key) {
+                        input::set_power_pad_button(emulator, button, true);
+                    }
+
+                    match bindings.find_hotkey(key) {
+                        Some(input::bindings::Hotkey::Pause) if !repeat => paused = !paused,
+                        Some(input::bindings::Hotkey::Reset) if !repeat => reset(emulator),
+                        Some(input::bindings::Hotkey::SaveState) if !repeat => save_state = Some(state::save(emulator)),
+                        Some(input::bindings::Hotkey::LoadState) if !repeat => {
+                            if let Some(saved) = &save_state {
+                                state::load(emulator, saved);
+                            }
+                        },
+                        Some(input::bindings::Hotkey::FastForward) => fast_forward = true,
+                        _ => {}
+                    }
+                },
+                Event::KeyUp { keycode: Some(key), .. } => {
+                    if let Some((player, button)) = bindings.find_key(key) {
+                        input::set_button(emulator, player, button, false);
+                    }
+
+                    if let Some(button) = bindings.find_power_pad_key(key) {
+                        input::set_power_pad_button(emulator, button, false);
+                    }
+
+                    if bindings.find_hotkey(key) == Some(input::bindings::Hotkey::FastForward) {
+                        fast_forward = false;
+                    }
+                },
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            println!("{} connected as player {}", controller.name(), controllers.len() + 1);
+                            controllers.push(controller);
+                        },
+                        Err(error) => println!("Problem opening controller: {:?}", error),
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|controller| controller.instance_id() != which);
+                },
+                Event::ControllerButtonDown { which, button, .. } => {
+                    if let Some(player) = controllers.iter().position(|controller| controller.instance_id() == which) {
+                        if let Some(nes_button) = bindings.find_button(button, player) {
+                            input::set_button(emulator, player, nes_button, true);
+                        }
+                    }
+                },
+                Event::ControllerButtonUp { which, button, .. } => {
+                    if let Some(player) = controllers.iter().position(|controller| controller.instance_id() == which) {
+                        if let Some(nes_button) = bindings.find_button(button, player) {
+                            input::set_button(emulator, player, nes_button, false);
+                        }
+                    }
+                },
+                // the zapper aims and the vaus knob follows wherever the mouse is, scaled from the window to the frame
+                Event::MouseMotion { x, y, .. } => {
+                    let (width, height) = canvas.window().size();
+                    input::set_mouse_position(emulator, x * ppu::SCREEN_WIDTH as i32 / width as i32, y * ppu::SCREEN_HEIGHT as i32 / height as i32);
+                },
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, .. } => {
+                    input::set_mouse_button(emulator, true);
+                },
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                    input::set_mouse_button(emulator, false);
+                },
+                Event::Window { win_event: WindowEvent::Leave, .. } => {
+                    input::set_mouse_position(emulator, -1, -1);
+                },
                 _ => {}
             }
         }
 
-        for _ in 1..29781 {
-            ppu::run_cycle(emulator);
-            ppu::run_cycle(emulator);
-            ppu::run_cycle(emulator);
+        if paused {
+            ::std::thread::sleep(frame_duration);
+            continue;
+        }
 
-            cpu::run_cycle(emulator);
+        // fast forward runs extra frames without showing them, their audio is dropped
+        if fast_forward {
+            for _ in 1..FAST_FORWARD_FRAMES {
+                run_frame(emulator);
+                audio.skip_frame(emulator);
+            }
         }
 
+        run_frame(emulator);
+
+        ppu::frame_rgb(emulator, &mut frame);
+        texture.update(None, &frame, (ppu::SCREEN_WIDTH * 3) as usize)
+            .map_err(|e| e.to_string())?;
+        canvas.copy(&texture, None, None)?;
         canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        audio.queue_frame(emulator)?;
+
+        // sleep off whatever is left of the frame, audio rate control absorbs the remaining drift
+        if let Some(remaining) = frame_duration.checked_sub(frame_start.elapsed()) {
+            ::std::thread::sleep(remaining);
+        }
     }
 
     Ok(())
@@ -0,0 +1,179 @@
+use crate::config;
+use crate::ppu;
+
+/*
+    background fetch pipeline, see https://www.nesdev.org/wiki/PPU_rendering
+
+    each tile takes 8 dots to fetch, two dots per memory access:
+    dot % 8 == 1    nametable byte
+    dot % 8 == 3    attribute byte
+    dot % 8 == 5    pattern table tile low
+    dot % 8 == 7    pattern table tile high (+8 bytes from low)
+
+    tiles are fetched during dots 1-256 for the current line, and the first two tiles
+    of the next line are fetched during dots 321-336. Fetch addresses come from the
+    loopy v register, which is stepped along with the fetches. The fetched bytes are loaded
+    into the low byte of the 16 bit shift registers on every 8th dot, and the registers
+    shift once per dot so the high byte always holds the tile currently being drawn.
+*/
+
+pub fn run_cycle(emulator: &mut config::Emulator) {
+    let cycle = emulator.ppu.cycle;
+
+    if (2..=257).contains(&cycle) || (322..=337).contains(&cycle) {
+        update_shifters(emulator);
+    }
+
+    if (1..=256).contains(&cycle) || (321..=336).contains(&cycle) {
+        match cycle % 8 {
+            1 => {
+                load_shifters(emulator);
+                fetch_nametable_byte(emulator);
+            },
+            3 => fetch_attribute_byte(emulator),
+            5 => fetch_pattern_byte(emulator, false),
+            7 => fetch_pattern_byte(emulator, true),
+            0 => increment_coarse_x(emulator),
+            _ => {}
+        }
+    }
+
+    if cycle == 256 {
+        increment_y(emulator);
+    }
+
+    if cycle == 257 {
+        load_shifters(emulator);
+        transfer_address_x(emulator);
+    }
+
+    // the pre render line reloads the vertical scroll for the next frame
+    if emulator.ppu.scanline == 261 && (280..=304).contains(&cycle) {
+        transfer_address_y(emulator);
+    }
+
+    // unused nametable fetches at the end of the line, some mappers watch for these
+    if cycle == 337 || cycle == 339 {
+        fetch_nametable_byte(emulator);
+    }
+}
+
+// returns the 2 bit pattern and 2 bit palette selected for the current dot
+pub fn pixel(emulator: &config::Emulator) -> (u8, u8) {
+    let mux: u16 = 0x8000 >> emulator.ppu.fine_x;
+
+    let pattern_lo = (emulator.ppu.bg_shifter_pattern_lo & mux != 0) as u8;
+    let pattern_hi = (emulator.ppu.bg_shifter_pattern_hi & mux != 0) as u8;
+    let attrib_lo = (emulator.ppu.bg_shifter_attrib_lo & mux != 0) as u8;
+    let attrib_hi = (emulator.ppu.bg_shifter_attrib_hi & mux != 0) as u8;
+
+    return ((pattern_hi << 1) | pattern_lo, (attrib_hi << 1) | attrib_lo);
+}
+
+/*
+    v and t share the same layout, see https://www.nesdev.org/wiki/PPU_scrolling
+
+    yyy NN YYYYY XXXXX
+    ||| || ||||| +++++-- coarse X scroll
+    ||| || +++++-------- coarse Y scroll
+    ||| ++-------------- nametable select
+    +++----------------- fine Y scroll
+*/
+
+// moves to the next tile, wrapping into the horizontally adjacent nametable after column 31
+pub fn increment_coarse_x(emulator: &mut config::Emulator) {
+    let v = &mut emulator.ppu.vram_addr;
+
+    if *v & 0x001F == 31 {
+        *v &= !0x001F;
+        *v ^= 0x0400;
+    } else {
+        *v += 1;
+    }
+}
+
+// moves to the next pixel row, row 29 is the last row of tiles so wrap into the vertically adjacent nametable
+pub fn increment_y(emulator: &mut config::Emulator) {
+    let v = &mut emulator.ppu.vram_addr;
+
+    if *v & 0x7000 != 0x7000 {
+        *v += 0x1000;
+        return;
+    }
+
+    *v &= !0x7000;
+    let mut coarse_y = (*v & 0x03E0) >> 5;
+    if coarse_y == 29 {
+        coarse_y = 0;
+        *v ^= 0x0800;
+    } else if coarse_y == 31 {
+        // rows 30 and 31 are the attribute table, scrolling into them wraps without switching nametable
+        coarse_y = 0;
+    } else {
+        coarse_y += 1;
+    }
+
+    *v = (*v & !0x03E0) | (coarse_y << 5);
+}
+
+fn transfer_address_x(emulator: &mut config::Emulator) {
+    emulator.ppu.vram_addr = (emulator.ppu.vram_addr & !0x041F) | (emulator.ppu.temp_vram_addr & 0x041F);
+}
+
+fn transfer_address_y(emulator: &mut config::Emulator) {
+    emulator.ppu.vram_addr = (emulator.ppu.vram_addr & !0x7BE0) | (emulator.ppu.temp_vram_addr & 0x7BE0);
+}
+
+fn fetch_nametable_byte(emulator: &mut config::Emulator) {
+    let address = 0x2000 | (emulator.ppu.vram_addr & 0x0FFF);
+    emulator.ppu.bg_next_tile_id = ppu::read_u8(emulator, address);
+}
+
+fn fetch_attribute_byte(emulator: &mut config::Emulator) {
+    let v = emulator.ppu.vram_addr;
+    let address = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+    let mut attrib = ppu::read_u8(emulator, address);
+
+    // each attribute byte covers a 4x4 tile area, pick the 2x2 quadrant this tile is in
+    if v & 0x0040 != 0 {
+        attrib >>= 4;
+    }
+    if v & 0x0002 != 0 {
+        attrib >>= 2;
+    }
+
+    emulator.ppu.bg_next_tile_attrib = attrib & 0x03;
+}
+
+fn fetch_pattern_byte(emulator: &mut config::Emulator, high: bool) {
+    let fine_y = (emulator.ppu.vram_addr >> 12) & 0x7;
+    let table = ((emulator.ppu.ppu_ctrl as u16 & 0x10) >> 4) << 12;
+    let address = table + ((emulator.ppu.bg_next_tile_id as u16) << 4) + fine_y + if high { 8 } else { 0 };
+    let value = ppu::read_u8(emulator, address);
+
+    if high {
+        emulator.ppu.bg_next_tile_msb = value;
+    } else {
+        emulator.ppu.bg_next_tile_lsb = value;
+    }
+}
+
+fn load_shifters(emulator: &mut config::Emulator) {
+    let ppu = &mut emulator.ppu;
+
+    ppu.bg_shifter_pattern_lo = (ppu.bg_shifter_pattern_lo & 0xFF00) | ppu.bg_next_tile_lsb as u16;
+    ppu.bg_shifter_pattern_hi = (ppu.bg_shifter_pattern_hi & 0xFF00) | ppu.bg_next_tile_msb as u16;
+
+    // attributes are per tile, so inflate the 2 bit palette to cover all 8 pixels
+    ppu.bg_shifter_attrib_lo = (ppu.bg_shifter_attrib_lo & 0xFF00) | if ppu.bg_next_tile_attrib & 0x1 != 0 { 0xFF } else { 0x00 };
+    ppu.bg_shifter_attrib_hi = (ppu.bg_shifter_attrib_hi & 0xFF00) | if ppu.bg_next_tile_attrib & 0x2 != 0 { 0xFF } else { 0x00 };
+}
+
+fn update_shifters(emulator: &mut config::Emulator) {
+    let ppu = &mut emulator.ppu;
+
+    ppu.bg_shifter_pattern_lo <<= 1;
+    ppu.bg_shifter_pattern_hi <<= 1;
+    ppu.bg_shifter_attrib_lo <<= 1;
+    ppu.bg_shifter_attrib_hi <<= 1;
+}
@@ -1,5 +1,9 @@
 use std::fmt;
 use crate::config;
+use crate::rom;
+mod background;
+mod sprite;
+pub mod palette;
 /* 
     ppu memory map https://www.nesdev.org/wiki/PPU_memory_map
 
@@ -20,13 +24,25 @@ use crate::config;
     $03, $07, $0B, $0F	Sprite X coordinate
 */
 
-//static SCREEN_WIDTH: u32 = 256;
-//static SCREEN_HEIGHT: u32 = 240;
+pub static SCREEN_WIDTH: u32 = 256;
+pub static SCREEN_HEIGHT: u32 = 240;
 
 // index 0 based
 static TOTAL_SCANLINES: u32 = 262; // Vblank beyond screen height, 261 is pre render line
 static CYCLES_PER_SCANLINE: u32 = 341;
 
+// how the 4 logical nametables are folded onto the 2KB of ciram, see https://www.nesdev.org/wiki/Mirroring
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mirroring {
+    Horizontal, // $2000 = $2400, $2800 = $2C00
+    Vertical, // $2000 = $2800, $2400 = $2C00
+    SingleScreenA, // all 4 use the lower 1KB
+    SingleScreenB, // all 4 use the upper 1KB
+    FourScreen, // extra 2KB on the cartridge, no mirroring
+    Mapped([usize; 4]), // mapper picks the 1KB page for each nametable, 0-1 are ciram and 2-3 the four screen ram
+}
+
+#[derive(Clone)]
 pub struct PPU {
     pub memory: [u8; 0x4000],
     pub oam: [u8; 0x100],
@@ -37,12 +53,48 @@ pub struct PPU {
     pub ppu_mask: u8,
     pub ppu_status: u8,
     pub oam_addr: u16,
-    pub ppu_scroll_latch: bool,
-    pub ppu_addr_latch: bool,
-    pub ppu_scroll: u8,
-    pub ppu_addr: u16,
+
+    // loopy registers shared by $2000/$2005/$2006, see https://www.nesdev.org/wiki/PPU_scrolling
+    pub vram_addr: u16, // v, current vram address
+    pub temp_vram_addr: u16, // t, address of the top left onscreen tile
+    pub fine_x: u8, // x, fine x scroll (3 bits)
+    pub write_latch: bool, // w, toggles between the first and second write of $2005/$2006
+
     pub ppu_data: u8,
     pub odd_frame: bool,
+    pub pal_timing: bool, // pal 2C07, swaps the red and green emphasis bits
+
+    // nmi line to the cpu, latched on the rising edge of vblank && nmi enable, polled between instructions
+    pub nmi_pending: bool,
+    pub suppress_vblank: bool, // set when $2002 is read on the dot before vblank starts
+
+    // rendered frame, one colour per pixel (256x240), 6 bit colour in the low bits and the emphasis bits above it
+    pub frame_buffer: [u16; 0xF000],
+    pub frame_complete: bool,
+    pub palette: palette::Palette,
+
+    // background pipeline, latches filled by the fetches then loaded into the shift registers every 8 dots
+    pub bg_next_tile_id: u8,
+    pub bg_next_tile_attrib: u8,
+    pub bg_next_tile_lsb: u8,
+    pub bg_next_tile_msb: u8,
+    pub bg_shifter_pattern_lo: u16,
+    pub bg_shifter_pattern_hi: u16,
+    pub bg_shifter_attrib_lo: u16,
+    pub bg_shifter_attrib_hi: u16,
+
+    // sprites found for the next line during evaluation
+    pub secondary_oam: [u8; 0x20],
+    pub secondary_sprite_count: u8,
+    pub secondary_sprite_zero: bool,
+
+    // sprite output units for the line being drawn, loaded from secondary oam during dots 257-320
+    pub sprite_count: u8,
+    pub sprite_zero_rendering: bool,
+    pub sprite_pattern_lo: [u8; 8],
+    pub sprite_pattern_hi: [u8; 8],
+    pub sprite_attrib: [u8; 8],
+    pub sprite_x: [u8; 8],
 }
 
 impl Default for PPU {
@@ -57,12 +109,42 @@ impl Default for PPU {
             ppu_mask: 0,
             ppu_status: 0,
             oam_addr: 0,
-            ppu_scroll_latch: false,
-            ppu_addr_latch: false,
-            ppu_scroll: 0,
-            ppu_addr: 0,
+
+            vram_addr: 0,
+            temp_vram_addr: 0,
+            fine_x: 0,
+            write_latch: false,
+
             ppu_data: 0,
             odd_frame: false,
+            pal_timing: false,
+
+            nmi_pending: false,
+            suppress_vblank: false,
+
+            frame_buffer: [0; 0xF000],
+            frame_complete: false,
+            palette: palette::Palette::default(),
+
+            bg_next_tile_id: 0,
+            bg_next_tile_attrib: 0,
+            bg_next_tile_lsb: 0,
+            bg_next_tile_msb: 0,
+            bg_shifter_pattern_lo: 0,
+            bg_shifter_pattern_hi: 0,
+            bg_shifter_attrib_lo: 0,
+            bg_shifter_attrib_hi: 0,
+
+            secondary_oam: [0xFF; 0x20],
+            secondary_sprite_count: 0,
+            secondary_sprite_zero: false,
+
+            sprite_count: 0,
+            sprite_zero_rendering: false,
+            sprite_pattern_lo: [0; 8],
+            sprite_pattern_hi: [0; 8],
+            sprite_attrib: [0; 8],
+            sprite_x: [0; 8],
         }
     }
 }
@@ -75,7 +157,7 @@ impl fmt::Display for PPU {
 
 pub fn mapped_address(addr: usize) -> usize {
     if addr > 0x2FFF && addr < 0x3F00 {
-        return 0x2000 + (addr & 0xEFF);
+        return 0x2000 + (addr & 0xFFF);
     }
 
     if addr > 0x3F1F {
@@ -85,11 +167,103 @@ pub fn mapped_address(addr: usize) -> usize {
     return addr;
 }
 
+// maps a $2000-$2FFF address onto the physical nametable it mirrors
+pub fn nametable_address(mirroring: Mirroring, addr: usize) -> usize {
+    let table = match mirroring {
+        Mirroring::Horizontal => (addr >> 11) & 0x1,
+        Mirroring::Vertical => (addr >> 10) & 0x1,
+        Mirroring::SingleScreenA => 0,
+        Mirroring::SingleScreenB => 1,
+        Mirroring::FourScreen => (addr >> 10) & 0x3,
+        Mirroring::Mapped(pages) => pages[(addr >> 10) & 0x3] & 0x3,
+    };
+
+    return 0x2000 + (table * 0x400) + (addr & 0x3FF);
+}
+
+fn physical_address(emulator: &config::Emulator, address: u16) -> usize {
+    let addr = mapped_address((address & 0x3FFF).into());
+
+    if (0x2000..0x3000).contains(&addr) {
+        return nametable_address(rom::mirroring(emulator), addr);
+    }
+
+    // the backdrop entries of the sprite palettes $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C
+    if addr >= 0x3F10 && addr & 0x3 == 0 {
+        return addr - 0x10;
+    }
+
+    return addr;
+}
+
+// ciram ($2000-$27FF in ppu memory) for a pattern table address the mapper points at it
+fn ciram_address(page: usize, address: u16) -> usize {
+    return 0x2000 + ((page & 0x1) * 0x400) + (address as usize & 0x3FF);
+}
+
+pub fn read_u8(emulator: &mut config::Emulator, address: u16) -> u8 {
+    // pattern tables are on the cartridge, unless the mapper puts ciram there
+    if address & 0x3FFF < 0x2000 {
+        if let Some(page) = rom::chr_ciram_page(emulator, address & 0x3FFF) {
+            return emulator.ppu.memory[ciram_address(page, address)];
+        }
+        return rom::ppu_read(emulator, address & 0x3FFF);
+    }
+
+    let addr = mapped_address((address & 0x3FFF).into());
+    if addr < 0x3000 {
+        if let Some(value) = rom::nametable_read(emulator, addr as u16) {
+            return value;
+        }
+    }
+
+    let addr = physical_address(emulator, address);
+
+    // palette ram is only 6 bits wide
+    if addr >= 0x3F00 {
+        return emulator.ppu.memory[addr] & 0x3F;
+    }
+
+    return emulator.ppu.memory[addr];
+}
+
+pub fn write_u8(emulator: &mut config::Emulator, address: u16, value: u8) {
+    if address & 0x3FFF < 0x2000 {
+        if let Some(page) = rom::chr_ciram_page(emulator, address & 0x3FFF) {
+            emulator.ppu.memory[ciram_address(page, address)] = value;
+            return;
+        }
+        rom::ppu_write(emulator, address & 0x3FFF, value);
+        return;
+    }
+
+    let addr = mapped_address((address & 0x3FFF).into());
+    if addr < 0x3000 && rom::nametable_write(emulator, addr as u16, value) {
+        return;
+    }
+
+    let addr = physical_address(emulator, address);
+    emulator.ppu.memory[addr] = value;
+}
+
+// converts the frame buffer to rgb24 for display
+pub fn frame_rgb(emulator: &config::Emulator, buffer: &mut [u8]) {
+    for (i, pixel) in emulator.ppu.frame_buffer.iter().enumerate() {
+        let (r, g, b) = emulator.ppu.palette.colour((pixel & 0x3F) as u8, (pixel >> 6) as u8);
+        buffer[i * 3] = r;
+        buffer[i * 3 + 1] = g;
+        buffer[i * 3 + 2] = b;
+    }
+}
+
 pub fn run_cycle(emulator: &mut config::Emulator) {
+    rom::ppu_cycle(emulator);
+
     match emulator.ppu.scanline {
         0..=239 => process_visible_scanline(emulator),
         240 => process_post_scanline(emulator),
         241 => process_nmi(emulator),
+        242..=260 => {},
         261 => process_pre_scanline(emulator),
         _ => println!("Invalid Scanline Detected")
     }
@@ -106,25 +280,117 @@ pub fn run_cycle(emulator: &mut config::Emulator) {
     }
 }
 
+pub fn rendering_enabled(emulator: &config::Emulator) -> bool {
+    return emulator.ppu.ppu_mask & 0x18 != 0;
+}
+
+fn draw_pixel(emulator: &mut config::Emulator) {
+    let x = emulator.ppu.cycle - 1;
+    let y = emulator.ppu.scanline;
+
+    let mask = emulator.ppu.ppu_mask;
+    let left_column = x < 8;
+
+    let (mut bg_pixel, mut bg_palette) = background::pixel(emulator);
+    if mask & 0x08 == 0 || (left_column && mask & 0x02 == 0) {
+        bg_pixel = 0;
+        bg_palette = 0;
+    }
+
+    let (mut sprite_pixel, sprite_palette, behind_background, sprite_zero) = sprite::pixel(emulator);
+    if mask & 0x10 == 0 || (left_column && mask & 0x04 == 0) {
+        sprite_pixel = 0;
+    }
+
+    // sprite 0 hit happens on any overlap of opaque pixels regardless of priority, except on the last column
+    if sprite_zero && bg_pixel != 0 && sprite_pixel != 0 && x != 255 {
+        set_sprite_zero_hit(emulator, true);
+    }
+
+    // sprite palettes are the upper 4 palettes, $3F10-$3F1F
+    let (pixel, palette) = if sprite_pixel != 0 && (bg_pixel == 0 || !behind_background) {
+        (sprite_pixel, sprite_palette + 4)
+    } else {
+        (bg_pixel, bg_palette)
+    };
+
+    // pixel value 0 is transparent and always shows the universal background colour
+    let mut palette_address = if pixel == 0 { 0x3F00 } else { 0x3F00 + ((palette as u16) << 2) + pixel as u16 };
+
+    // with rendering off, pointing v into palette ram shows that entry instead of the backdrop
+    if !rendering_enabled(emulator) && emulator.ppu.vram_addr & 0x3F00 == 0x3F00 {
+        palette_address = emulator.ppu.vram_addr;
+    }
+
+    let mut colour = read_u8(emulator, palette_address) & 0x3F;
+    if mask & 0x01 != 0 {
+        colour &= 0x30; // greyscale, only the grey column of each brightness row
+    }
+
+    emulator.ppu.frame_buffer[(y * SCREEN_WIDTH + x) as usize] = colour as u16 | ((emphasis(emulator) as u16) << 6);
+}
+
+// emphasis bits of ppumask in red, green, blue order, pal consoles have red and green swapped
+pub fn emphasis(emulator: &config::Emulator) -> u8 {
+    let bits = emulator.ppu.ppu_mask >> 5;
+
+    if emulator.ppu.pal_timing {
+        return (bits & 0x4) | ((bits & 0x1) << 1) | ((bits & 0x2) >> 1);
+    }
+
+    return bits;
+}
+
+// fetches are shared between the visible lines and the pre render line, which primes the first two tiles of line 0
+fn process_render_fetches(emulator: &mut config::Emulator) {
+    if rendering_enabled(emulator) {
+        background::run_cycle(emulator);
+        sprite::run_cycle(emulator);
+    }
+}
+
 fn process_visible_scanline(emulator: &mut config::Emulator) {
+    process_render_fetches(emulator);
 
+    if (1..=256).contains(&emulator.ppu.cycle) {
+        draw_pixel(emulator);
+    }
 }
 
 fn process_post_scanline(emulator: &mut config::Emulator) {
-    
+    if emulator.ppu.cycle == 0 {
+        emulator.ppu.frame_complete = true;
+    }
 }
 
 fn process_nmi(emulator: &mut config::Emulator) {
-    
+    if emulator.ppu.cycle != 1 {
+        return;
+    }
+
+    // a $2002 read racing the flag being set means the flag stays clear and no nmi happens this frame
+    if emulator.ppu.suppress_vblank {
+        emulator.ppu.suppress_vblank = false;
+        return;
+    }
+
+    set_vblank(emulator, true);
+    if nmi_enabled(emulator) {
+        emulator.ppu.nmi_pending = true;
+    }
 }
 
 fn process_pre_scanline(emulator: &mut config::Emulator) {
-    if emulator.ppu.odd_frame && emulator.ppu.cycle == CYCLES_PER_SCANLINE - 1 {
+    process_render_fetches(emulator);
+
+    if emulator.ppu.odd_frame && rendering_enabled(emulator) && emulator.ppu.cycle == CYCLES_PER_SCANLINE - 1 {
         emulator.ppu.cycle += 1; // if odd frame skip last cycle
     }
 
     if emulator.ppu.cycle == 1 {
         set_vblank(emulator, false);
+        set_sprite_zero_hit(emulator, false);
+        set_sprite_overflow(emulator, false);
     }
 }
 
@@ -135,16 +401,120 @@ pub fn reset(emulator: &mut config::Emulator) {
     emulator.ppu.ppu_mask = 0;
     emulator.ppu.ppu_status = 0;
     emulator.ppu.oam_addr = 0;
-    emulator.ppu.ppu_scroll_latch = false;
-    emulator.ppu.ppu_addr_latch = false;
-    emulator.ppu.ppu_scroll = 0;
-    emulator.ppu.ppu_addr = 0;
+    emulator.ppu.vram_addr = 0;
+    emulator.ppu.temp_vram_addr = 0;
+    emulator.ppu.fine_x = 0;
+    emulator.ppu.write_latch = false;
     emulator.ppu.ppu_data = 0;
     emulator.ppu.odd_frame = false;
+    emulator.ppu.pal_timing = emulator.rom.header.pal_tv;
+    emulator.ppu.nmi_pending = false;
+    emulator.ppu.suppress_vblank = false;
+    emulator.ppu.frame_complete = false;
+    emulator.ppu.bg_shifter_pattern_lo = 0;
+    emulator.ppu.bg_shifter_pattern_hi = 0;
+    emulator.ppu.bg_shifter_attrib_lo = 0;
+    emulator.ppu.bg_shifter_attrib_hi = 0;
+    emulator.ppu.secondary_sprite_count = 0;
+    emulator.ppu.secondary_sprite_zero = false;
+    emulator.ppu.sprite_count = 0;
+    emulator.ppu.sprite_zero_rendering = false;
 }
 
 pub fn get_control_increment_mode(emulator: &mut config::Emulator) -> bool {
-    return emulator.ppu.ppu_status & 0x04 == 0x04;
+    return emulator.ppu.ppu_ctrl & 0x04 == 0x04;
+}
+
+// steps v after a $2007 access, while rendering the access instead bumps both coarse x and y like the fetches do
+pub fn increment_vram_addr(emulator: &mut config::Emulator) {
+    if rendering_line(emulator) && rendering_enabled(emulator) {
+        background::increment_coarse_x(emulator);
+        background::increment_y(emulator);
+        return;
+    }
+
+    // If set to vertical mode, the increment is 32, so it skips
+    // one whole nametable row; in horizontal mode it just increments
+    // by 1, moving to the next column
+    let mut ppu_increment = 1;
+    if get_control_increment_mode(emulator) {
+        ppu_increment = 32;
+    }
+
+    emulator.ppu.vram_addr = (emulator.ppu.vram_addr + ppu_increment) & 0x7FFF;
+}
+
+pub fn nmi_enabled(emulator: &config::Emulator) -> bool {
+    return emulator.ppu.ppu_ctrl & 0x80 == 0x80;
+}
+
+pub fn in_vblank(emulator: &config::Emulator) -> bool {
+    return emulator.ppu.ppu_status & 0x80 == 0x80;
+}
+
+// $2000 write, enabling nmi while the vblank flag is still set raises another nmi straight away
+pub fn write_ctrl(emulator: &mut config::Emulator, value: u8) {
+    let was_enabled = nmi_enabled(emulator);
+    emulator.ppu.ppu_ctrl = value;
+
+    if !was_enabled && nmi_enabled(emulator) && in_vblank(emulator) {
+        emulator.ppu.nmi_pending = true;
+    }
+}
+
+// $2002 read, see https://www.nesdev.org/wiki/PPU_frame_timing#VBL_Flag_Timing for the race with the flag being set
+pub fn read_status(emulator: &mut config::Emulator) -> u8 {
+    if emulator.ppu.scanline == 241 {
+        match emulator.ppu.cycle {
+            // the flag is set on the next dot, reading now reads it clear and stops it being set at all
+            1 => emulator.ppu.suppress_vblank = true,
+            // the flag was just set, it reads back set but the nmi is cancelled
+            2 | 3 => emulator.ppu.nmi_pending = false,
+            _ => {}
+        }
+    }
+
+    // lower 5 bits are open bus, the last value on the ppu data bus is the closest thing we track
+    let data = (emulator.ppu.ppu_status & 0xE0) | (emulator.ppu.ppu_data & 0x1F);
+
+    // Clear the vertical blanking flag
+    set_vblank(emulator, false);
+    // Reset Loopy's Address latch flag
+    emulator.ppu.write_latch = false;
+
+    return data;
+}
+
+pub fn rendering_line(emulator: &config::Emulator) -> bool {
+    return emulator.ppu.scanline < 240 || emulator.ppu.scanline == 261;
+}
+
+// $2004 read, does not increment oam_addr
+pub fn read_oam_data(emulator: &mut config::Emulator) -> u8 {
+    // secondary oam is being cleared during these dots and the clear reads $FF from oam
+    if rendering_line(emulator) && rendering_enabled(emulator) && (1..=64).contains(&emulator.ppu.cycle) {
+        return 0xFF;
+    }
+
+    let mut data = emulator.ppu.oam[emulator.ppu.oam_addr as usize];
+    // bits 2-4 of the attribute byte don't exist in oam and read back as 0
+    if emulator.ppu.oam_addr & 0x03 == 0x02 {
+        data &= 0xE3;
+    }
+
+    return data;
+}
+
+// $2004 write
+pub fn write_oam_data(emulator: &mut config::Emulator, value: u8) {
+    // writes during rendering are ignored, but still bump the address as if a whole sprite was skipped
+    if rendering_line(emulator) && rendering_enabled(emulator) {
+        emulator.ppu.oam_addr = (emulator.ppu.oam_addr + 4) & 0xFF;
+        return;
+    }
+
+    emulator.ppu.oam[emulator.ppu.oam_addr as usize] = value;
+    emulator.ppu.oam_addr = (emulator.ppu.oam_addr + 1) & 0xFF;
 }
 
 pub fn set_vblank(emulator: &mut config::Emulator, value: bool) {
@@ -153,4 +523,20 @@ pub fn set_vblank(emulator: &mut config::Emulator, value: bool) {
     } else {
         emulator.ppu.ppu_status &= 0b0111_1111;
     }
-}
\ No newline at end of file
+}
+
+pub fn set_sprite_zero_hit(emulator: &mut config::Emulator, value: bool) {
+    if value {
+        emulator.ppu.ppu_status |= 0b0100_0000;
+    } else {
+        emulator.ppu.ppu_status &= 0b1011_1111;
+    }
+}
+
+pub fn set_sprite_overflow(emulator: &mut config::Emulator, value: bool) {
+    if value {
+        emulator.ppu.ppu_status |= 0b0010_0000;
+    } else {
+        emulator.ppu.ppu_status &= 0b1101_1111;
+    }
+}
@@ -0,0 +1,105 @@
+use std::io;
+use std::io::Read;
+use std::fs::File;
+
+/*
+    converts the 6 bit colour indexes the ppu outputs into rgb, see https://www.nesdev.org/wiki/PPU_palettes
+
+    .pal files come in two sizes:
+    192 bytes   64 colours, rgb triplets
+    1536 bytes  the 64 colours repeated for each of the 8 combinations of the ppumask emphasis bits
+*/
+
+pub static COLOURS: usize = 64;
+pub static EMPHASIS_SETS: usize = 8;
+
+// how much the channels that aren't emphasized are dimmed, when the palette has no emphasis data of its own
+static EMPHASIS_ATTENUATION: f32 = 0.816328;
+
+// standard 2C02 ntsc palette
+pub static NTSC_PALETTE: [u8; 0xC0] = [
+    84, 84, 84,     0, 30, 116,     8, 16, 144,     48, 0, 136,
+    68, 0, 100,     92, 0, 48,      84, 4, 0,       60, 24, 0,
+    32, 42, 0,      8, 58, 0,       0, 64, 0,       0, 60, 0,
+    0, 50, 60,      0, 0, 0,        0, 0, 0,        0, 0, 0,
+
+    152, 150, 152,  8, 76, 196,     48, 50, 236,    92, 30, 228,
+    136, 20, 176,   160, 20, 100,   152, 34, 32,    120, 60, 0,
+    84, 90, 0,      40, 114, 0,     8, 124, 0,      0, 118, 40,
+    0, 102, 120,    0, 0, 0,        0, 0, 0,        0, 0, 0,
+
+    236, 238, 236,  76, 154, 236,   120, 124, 236,  176, 98, 236,
+    228, 84, 236,   236, 88, 180,   236, 106, 100,  212, 136, 32,
+    160, 170, 0,    116, 196, 0,    76, 208, 32,    56, 204, 108,
+    56, 180, 204,   60, 60, 60,     0, 0, 0,        0, 0, 0,
+
+    236, 238, 236,  168, 204, 236,  188, 188, 236,  212, 178, 236,
+    236, 174, 236,  236, 174, 212,  236, 180, 176,  228, 196, 144,
+    204, 210, 120,  180, 222, 120,  168, 226, 144,  152, 226, 180,
+    160, 214, 228,  160, 162, 160,  0, 0, 0,        0, 0, 0,
+];
+
+#[derive(Clone)]
+pub struct Palette {
+    pub rgb: [u8; 0x600], // 8 emphasis sets of 64 rgb triplets
+    pub has_emphasis: bool, // false if only the base 64 colours were provided
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        let mut palette = Palette {
+            rgb: [0; 0x600],
+            has_emphasis: false,
+        };
+
+        palette.load_colours(&NTSC_PALETTE);
+        return palette;
+    }
+}
+
+impl Palette {
+    pub fn load_file(&mut self, file_name: &String) -> io::Result<()> {
+        let mut f = File::open(file_name)?;
+        let mut buffer = Vec::new();
+        f.read_to_end(&mut buffer)?;
+
+        if buffer.len() != COLOURS * 3 && buffer.len() != COLOURS * 3 * EMPHASIS_SETS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("palette must be 192 or 1536 bytes, got {}", buffer.len())));
+        }
+
+        self.load_colours(&buffer);
+        Ok(())
+    }
+
+    fn load_colours(&mut self, buffer: &[u8]) {
+        self.has_emphasis = buffer.len() == self.rgb.len();
+
+        if self.has_emphasis {
+            self.rgb.copy_from_slice(buffer);
+            return;
+        }
+
+        // without emphasis data, build each set by dimming the channels that set doesn't emphasize
+        for (emphasis, set) in self.rgb.chunks_mut(COLOURS * 3).enumerate() {
+            set.copy_from_slice(buffer);
+
+            if emphasis == 0 {
+                continue;
+            }
+
+            for colour in set.chunks_mut(3) {
+                for (channel, value) in colour.iter_mut().enumerate() {
+                    if emphasis & (1 << channel) == 0 {
+                        *value = (*value as f32 * EMPHASIS_ATTENUATION) as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    // colour is the 6 bit palette value, emphasis the 3 ppumask emphasis bits in red, green, blue order
+    pub fn colour(&self, colour: u8, emphasis: u8) -> (u8, u8, u8) {
+        let index = (((emphasis as usize & 0x7) * COLOURS) + (colour as usize & 0x3F)) * 3;
+        return (self.rgb[index], self.rgb[index + 1], self.rgb[index + 2]);
+    }
+}
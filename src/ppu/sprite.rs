@@ -0,0 +1,178 @@
+use crate::config;
+use crate::ppu;
+
+/*
+    sprite evaluation and rendering, see https://www.nesdev.org/wiki/PPU_sprite_evaluation
+
+    dots 1-64       secondary oam is cleared to $FF
+    dots 65-256     oam is scanned for sprites on the next line, up to 8 are copied to secondary oam
+    dots 257-320    pattern data for the 8 sprite slots is fetched into the output units, 8 dots per slot
+
+    sprites are drawn one line below their oam y coordinate, since they are evaluated on the line before.
+
+    oam attribute byte
+    76543210
+    ||||||++- palette (4 to 7) of sprite
+    |||+++--- unimplemented
+    ||+------ priority (0: in front of background; 1: behind background)
+    |+------- flip sprite horizontally
+    +-------- flip sprite vertically
+*/
+
+pub static MAX_SPRITES_PER_LINE: usize = 8;
+
+pub fn run_cycle(emulator: &mut config::Emulator) {
+    let cycle = emulator.ppu.cycle;
+    let visible_line = emulator.ppu.scanline < 240;
+
+    if cycle == 64 {
+        emulator.ppu.secondary_oam = [0xFF; 0x20];
+    }
+
+    // the pre render line does not evaluate sprites, so line 0 never has any
+    if cycle == 256 {
+        if visible_line {
+            evaluate(emulator);
+        } else {
+            emulator.ppu.secondary_sprite_count = 0;
+            emulator.ppu.secondary_sprite_zero = false;
+        }
+    }
+
+    if (257..=320).contains(&cycle) {
+        emulator.ppu.oam_addr = 0;
+
+        let slot = ((cycle - 257) / 8) as usize;
+        match (cycle - 257) % 8 {
+            0 => load_slot(emulator, slot),
+            4 => fetch_pattern_byte(emulator, slot, false),
+            6 => fetch_pattern_byte(emulator, slot, true),
+            _ => {}
+        }
+    }
+}
+
+// returns the 2 bit pattern, 2 bit palette, behind background priority and if it's sprite 0 for the current dot
+pub fn pixel(emulator: &config::Emulator) -> (u8, u8, bool, bool) {
+    let x = emulator.ppu.cycle - 1;
+
+    for slot in 0..emulator.ppu.sprite_count as usize {
+        let offset = x as i32 - emulator.ppu.sprite_x[slot] as i32;
+        if !(0..8).contains(&offset) {
+            continue;
+        }
+
+        // patterns are already flipped when fetched, bit 7 is the leftmost pixel
+        let bit = 7 - offset;
+        let pattern_lo = (emulator.ppu.sprite_pattern_lo[slot] >> bit) & 0x1;
+        let pattern_hi = (emulator.ppu.sprite_pattern_hi[slot] >> bit) & 0x1;
+        let pattern = (pattern_hi << 1) | pattern_lo;
+
+        // lower slots win, even when the higher slot would have been in front of the background
+        if pattern != 0 {
+            let attrib = emulator.ppu.sprite_attrib[slot];
+            return (pattern, attrib & 0x03, attrib & 0x20 != 0, slot == 0 && emulator.ppu.sprite_zero_rendering);
+        }
+    }
+
+    return (0, 0, false, false);
+}
+
+pub fn sprite_height(emulator: &config::Emulator) -> u32 {
+    if emulator.ppu.ppu_ctrl & 0x20 != 0 {
+        return 16;
+    }
+
+    return 8;
+}
+
+fn in_range(emulator: &config::Emulator, y: u8) -> bool {
+    let row = emulator.ppu.scanline as i32 - y as i32;
+    return row >= 0 && row < sprite_height(emulator) as i32;
+}
+
+fn evaluate(emulator: &mut config::Emulator) {
+    let mut count: usize = 0;
+    let mut n: usize = 0;
+
+    emulator.ppu.secondary_sprite_zero = false;
+
+    while n < 64 && count < MAX_SPRITES_PER_LINE {
+        let y = emulator.ppu.oam[n * 4];
+        if in_range(emulator, y) {
+            emulator.ppu.secondary_oam[count * 4..count * 4 + 4].copy_from_slice(&emulator.ppu.oam[n * 4..n * 4 + 4]);
+            if n == 0 {
+                emulator.ppu.secondary_sprite_zero = true;
+            }
+            count += 1;
+        }
+        n += 1;
+    }
+
+    emulator.ppu.secondary_sprite_count = count as u8;
+
+    // once 8 sprites are found the hardware keeps scanning for overflow, but increments the byte
+    // offset along with the sprite index, so it ends up treating tile, attribute and x bytes as y coordinates
+    let mut m: usize = 0;
+    while n < 64 {
+        let y = emulator.ppu.oam[n * 4 + m];
+        if in_range(emulator, y) {
+            ppu::set_sprite_overflow(emulator, true);
+            break;
+        }
+        n += 1;
+        m = (m + 1) & 0x3;
+    }
+}
+
+// copies secondary oam into the output unit for the slot, empty slots still fetch tile $FF
+fn load_slot(emulator: &mut config::Emulator, slot: usize) {
+    if slot == 0 {
+        emulator.ppu.sprite_count = emulator.ppu.secondary_sprite_count;
+        emulator.ppu.sprite_zero_rendering = emulator.ppu.secondary_sprite_zero;
+    }
+
+    emulator.ppu.sprite_attrib[slot] = emulator.ppu.secondary_oam[slot * 4 + 2];
+    emulator.ppu.sprite_x[slot] = emulator.ppu.secondary_oam[slot * 4 + 3];
+}
+
+fn pattern_address(emulator: &config::Emulator, slot: usize) -> u16 {
+    let y = emulator.ppu.secondary_oam[slot * 4];
+    let tile = emulator.ppu.secondary_oam[slot * 4 + 1];
+    let attrib = emulator.ppu.secondary_oam[slot * 4 + 2];
+    let height = sprite_height(emulator);
+
+    let mut row = (emulator.ppu.scanline as i32 - y as i32).clamp(0, height as i32 - 1) as u16;
+    if attrib & 0x80 != 0 {
+        row = height as u16 - 1 - row;
+    }
+
+    if height == 16 {
+        // 8x16 sprites pick the table with bit 0 of the tile, and the bottom half is the next tile
+        let table = (tile as u16 & 0x01) << 12;
+        let tile = (tile as u16 & 0xFE) + (row >> 3);
+        return table | (tile << 4) | (row & 0x7);
+    }
+
+    let table = ((emulator.ppu.ppu_ctrl as u16 & 0x08) >> 3) << 12;
+    return table | ((tile as u16) << 4) | row;
+}
+
+fn fetch_pattern_byte(emulator: &mut config::Emulator, slot: usize, high: bool) {
+    let address = pattern_address(emulator, slot) + if high { 8 } else { 0 };
+    let mut value = ppu::read_u8(emulator, address);
+
+    if slot >= emulator.ppu.sprite_count as usize {
+        value = 0;
+    }
+
+    if emulator.ppu.sprite_attrib[slot] & 0x40 != 0 {
+        value = value.reverse_bits();
+    }
+
+    if high {
+        emulator.ppu.sprite_pattern_hi[slot] = value;
+    } else {
+        emulator.ppu.sprite_pattern_lo[slot] = value;
+    }
+}
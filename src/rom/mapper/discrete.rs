@@ -0,0 +1,171 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    discrete logic boards, a latch or two in front of the bank lines, see https://www.nesdev.org/wiki/Category:Discrete_logic_mappers
+
+    UxROM (2)           $8000 16KB prg bank at $8000, last bank fixed at $C000
+    CNROM (3)           $8000 8KB chr bank
+    AxROM (7)           $8000 32KB prg bank in bits 0-2, bit 4 picks the one screen nametable
+    BNROM (34)          $8000 32KB prg bank
+    NINA-001 (34)       $7FFD 32KB prg bank, $7FFE/$7FFF 4KB chr banks at $0000/$1000, with 8KB prg ram
+    Color Dreams (11)   $8000 32KB prg bank in bits 0-1, 8KB chr bank in bits 4-7
+    GxROM (66)          $8000 32KB prg bank in bits 4-5, 8KB chr bank in bits 0-1
+
+    on boards with bus conflicts the rom drives the data bus during the write too, so the latch sees
+    the written value ANDed with the rom byte at that address. BNROM, Color Dreams and GxROM always have them.
+    UxROM, CNROM and AxROM come both ways, NES 2.0 submapper 1 means none and 2 means conflicts. Plain iNES
+    files can also ask for them with the flag in byte 10, in NES 2.0 files that byte is ram sizes instead.
+*/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Board {
+    UxROM,
+    CNROM,
+    AxROM,
+    BNROM,
+    NINA001,
+    ColorDreams,
+    GxROM,
+}
+
+#[derive(Clone)]
+pub struct Discrete {
+    pub cartridge: mapper::Cartridge,
+    pub board: Board,
+    pub bus_conflicts: bool,
+    pub prg_bank: usize,
+    pub chr_banks: [usize; 2], // 4KB banks at $0000 and $1000
+    pub mirroring: ppu::Mirroring,
+}
+
+impl Discrete {
+    pub fn new(rom: &rom::ROM, board: Board) -> Discrete {
+        let bus_conflicts = match board {
+            Board::BNROM | Board::ColorDreams | Board::GxROM => true,
+            Board::NINA001 => false,
+            _ if rom.header.nes_2 => rom.header.submapper == 2,
+            _ => rom.header.bus_conflicts,
+        };
+
+        Discrete {
+            cartridge: mapper::Cartridge::new(rom),
+            board: board,
+            bus_conflicts: bus_conflicts,
+            prg_bank: 0,
+            chr_banks: [0, 1],
+            mirroring: if board == Board::AxROM { ppu::Mirroring::SingleScreenA } else { rom.header.mirroring() },
+        }
+    }
+
+    // mapper 34 is two unrelated boards, submapper 1 or chr rom means NINA-001
+    pub fn new_34(rom: &rom::ROM) -> Discrete {
+        if rom.header.submapper == 1 || (rom.header.submapper == 0 && !rom.chr_rom.is_empty()) {
+            return Discrete::new(rom, Board::NINA001);
+        }
+
+        return Discrete::new(rom, Board::BNROM);
+    }
+
+    fn set_chr_8k(&mut self, bank: usize) {
+        self.chr_banks = [bank * 2, bank * 2 + 1];
+    }
+
+    fn write_latch(&mut self, value: u8) {
+        let value = value as usize;
+
+        match self.board {
+            Board::UxROM => self.prg_bank = value,
+            Board::CNROM => self.set_chr_8k(value),
+            Board::AxROM => {
+                self.prg_bank = value & 0x07;
+                self.mirroring = if value & 0x10 != 0 { ppu::Mirroring::SingleScreenB } else { ppu::Mirroring::SingleScreenA };
+            },
+            Board::BNROM => self.prg_bank = value,
+            Board::ColorDreams => {
+                self.prg_bank = value & 0x03;
+                self.set_chr_8k(value >> 4);
+            },
+            Board::GxROM => {
+                self.prg_bank = (value >> 4) & 0x03;
+                self.set_chr_8k(value & 0x03);
+            },
+            Board::NINA001 => {}
+        }
+    }
+}
+
+impl mapper::Mapper for Discrete {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF if self.board == Board::NINA001 => self.cartridge.read_prg_ram(address as usize - 0x6000),
+            0x8000..=0xFFFF => {
+                let offset = address as usize - 0x8000;
+
+                if self.board == Board::UxROM {
+                    let bank = if address < 0xC000 { self.prg_bank } else { self.cartridge.prg_banks(0x4000) - 1 };
+                    return Some(self.cartridge.read_prg(0x4000, bank, offset));
+                }
+
+                // CNROM has no prg banking, 16KB roms are mirrored like NROM
+                if self.board == Board::CNROM {
+                    return Some(self.cartridge.read_prg(0x8000, 0, offset));
+                }
+
+                return Some(self.cartridge.read_prg(0x8000, self.prg_bank, offset));
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x6000..=0x7FFF if self.board == Board::NINA001 => {
+                self.cartridge.write_prg_ram(address as usize - 0x6000, value);
+
+                // the registers sit on top of the last 3 bytes of ram, writes go to both
+                match address {
+                    0x7FFD => self.prg_bank = (value & 0x1) as usize,
+                    0x7FFE => self.chr_banks[0] = (value & 0x0F) as usize,
+                    0x7FFF => self.chr_banks[1] = (value & 0x0F) as usize,
+                    _ => {}
+                }
+            },
+            0x8000..=0xFFFF if self.board != Board::NINA001 => {
+                let mut value = value;
+                if self.bus_conflicts {
+                    value &= self.cpu_read(address).unwrap_or(0xFF);
+                }
+                self.write_latch(value);
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let bank = self.chr_banks[(address >> 12) as usize & 0x1];
+        return self.cartridge.read_chr(0x1000, bank, address as usize & 0x0FFF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_banks[(address >> 12) as usize & 0x1];
+        self.cartridge.write_chr(0x1000, bank, address as usize & 0x0FFF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.mirroring;
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        if self.board != Board::NINA001 {
+            return None;
+        }
+
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
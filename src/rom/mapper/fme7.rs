@@ -0,0 +1,177 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    Sunsoft FME-7 and 5B (mapper 69), see https://www.nesdev.org/wiki/Sunsoft_FME-7
+
+    $8000-$9FFF     command, picks the register the next parameter write goes to
+    $A000-$BFFF     parameter
+    $C000-$DFFF     5B only, audio register select
+    $E000-$FFFF     5B only, audio register data
+
+    commands
+    $0-$7           1KB chr banks
+    $8              $6000 bank, bits 0-5 bank, bit 6 picks ram (1) or rom (0), bit 7 enables ram
+    $9-$B           8KB prg banks at $8000, $A000 and $C000, $E000 is fixed to the last bank
+    $C              mirroring (0: vertical; 1: horizontal; 2: single screen A; 3: single screen B)
+    $D              irq control, bit 0 enables the irq and bit 7 the counter, any write acknowledges
+    $E              irq counter low byte
+    $F              irq counter high byte
+
+    the irq counter is 16 bits and counts down every cpu cycle, firing when it wraps from 0 to $FFFF.
+*/
+
+#[derive(Clone)]
+pub struct FME7 {
+    pub cartridge: mapper::Cartridge,
+    pub command: u8,
+    pub chr_banks: [usize; 8],
+    pub prg_ram_bank: u8, // command $8
+    pub prg_banks: [usize; 3],
+    pub mirroring: ppu::Mirroring,
+
+    pub irq_enabled: bool,
+    pub irq_counter_enabled: bool,
+    pub irq_counter: u16,
+    pub irq_pending: bool,
+
+    // 5B expansion audio registers, kept so the sound chip can be added on top, nothing plays them yet
+    pub audio_select: u8,
+    pub audio_registers: [u8; 0x10],
+}
+
+impl FME7 {
+    pub fn new(rom: &rom::ROM) -> FME7 {
+        FME7 {
+            cartridge: mapper::Cartridge::new(rom),
+            command: 0,
+            chr_banks: [0; 8],
+            prg_ram_bank: 0,
+            prg_banks: [0; 3],
+            mirroring: rom.header.mirroring(),
+
+            irq_enabled: false,
+            irq_counter_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+
+            audio_select: 0,
+            audio_registers: [0; 0x10],
+        }
+    }
+
+    fn prg_ram_selected(&self) -> bool {
+        return self.prg_ram_bank & 0x40 != 0;
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        return self.prg_ram_bank & 0xC0 == 0xC0;
+    }
+
+    fn write_parameter(&mut self, value: u8) {
+        match self.command {
+            0x0..=0x7 => self.chr_banks[self.command as usize] = value as usize,
+            0x8 => self.prg_ram_bank = value,
+            0x9..=0xB => self.prg_banks[(self.command - 0x9) as usize] = (value & 0x3F) as usize,
+            0xC => {
+                self.mirroring = match value & 0x3 {
+                    0 => ppu::Mirroring::Vertical,
+                    1 => ppu::Mirroring::Horizontal,
+                    2 => ppu::Mirroring::SingleScreenA,
+                    _ => ppu::Mirroring::SingleScreenB,
+                };
+            },
+            0xD => {
+                self.irq_enabled = value & 0x01 != 0;
+                self.irq_counter_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            },
+            0xE => self.irq_counter = (self.irq_counter & 0xFF00) | value as u16,
+            _ => self.irq_counter = (self.irq_counter & 0x00FF) | ((value as u16) << 8),
+        }
+    }
+}
+
+impl mapper::Mapper for FME7 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => {
+                let offset = address as usize & 0x1FFF;
+                let bank = (self.prg_ram_bank & 0x3F) as usize;
+
+                if !self.prg_ram_selected() {
+                    return Some(self.cartridge.read_prg(0x2000, bank, offset));
+                }
+                if !self.prg_ram_enabled() {
+                    return None;
+                }
+                return self.cartridge.read_prg_ram(bank * 0x2000 + offset);
+            },
+            0x8000..=0xFFFF => {
+                let window = ((address - 0x8000) >> 13) as usize;
+                let bank = if window < 3 { self.prg_banks[window] } else { self.cartridge.prg_banks(0x2000) - 1 };
+                return Some(self.cartridge.read_prg(0x2000, bank, address as usize & 0x1FFF));
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x6000..=0x7FFF => {
+                if self.prg_ram_enabled() {
+                    let bank = (self.prg_ram_bank & 0x3F) as usize;
+                    self.cartridge.write_prg_ram(bank * 0x2000 + (address as usize & 0x1FFF), value);
+                }
+            },
+            0x8000..=0x9FFF => self.command = value & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(value),
+            0xC000..=0xDFFF => self.audio_select = value,
+            0xE000..=0xFFFF => {
+                // the upper nibble of the select has to be 0 for the write to reach the sound chip
+                if self.audio_select & 0xF0 == 0 {
+                    self.audio_registers[self.audio_select as usize] = value;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let bank = self.chr_banks[(address >> 10) as usize & 0x7];
+        return self.cartridge.read_chr(0x400, bank, address as usize & 0x3FF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_banks[(address >> 10) as usize & 0x7];
+        self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.mirroring;
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn cpu_cycle(&mut self) {
+        if !self.irq_counter_enabled {
+            return;
+        }
+
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+        if self.irq_counter == 0xFFFF && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    MMC1 (mapper 1), see https://www.nesdev.org/wiki/MMC1
+
+    registers are loaded serially, writing bit 0 of 5 consecutive writes to $8000-$FFFF. The address of
+    the 5th write picks the register. Writing a value with bit 7 set resets the shift register.
+    $8000-$9FFF     control
+    $A000-$BFFF     chr bank 0
+    $C000-$DFFF     chr bank 1
+    $E000-$FFFF     prg bank
+
+    control
+    43210
+    |||++- mirroring (0: one screen lower; 1: one screen upper; 2: vertical; 3: horizontal)
+    |++--- prg mode (0, 1: 32KB at $8000; 2: first bank fixed at $8000; 3: last bank fixed at $C000)
+    +----- chr mode (0: 8KB; 1: two 4KB banks)
+
+    prg bank
+    43210
+    |++++- 16KB prg bank
+    +----- prg ram disable
+
+    the SxROM boards reuse the upper chr bank bits for the prg rom and ram the MMC1 can't address itself
+    SOROM   16KB prg ram, bit 3 picks the 8KB ram bank
+    SUROM   512KB prg rom, bit 4 picks the 256KB half, the fixed bank included
+    SXROM   both of the above, with 32KB prg ram banked by bits 2-3
+*/
+
+#[derive(Clone)]
+pub struct MMC1 {
+    pub cartridge: mapper::Cartridge,
+    pub shift: u8,
+    pub shift_count: u8,
+    pub control: u8,
+    pub chr_bank_0: u8,
+    pub chr_bank_1: u8,
+    pub prg_bank: u8,
+    pub cycle: u64,
+    pub last_write_cycle: Option<u64>,
+    pub chr_a12: bool, // which chr bank register the last pattern fetch used in 4KB mode
+}
+
+impl MMC1 {
+    pub fn new(rom: &rom::ROM) -> MMC1 {
+        MMC1 {
+            cartridge: mapper::Cartridge::new(rom),
+            shift: 0,
+            shift_count: 0,
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycle: 0,
+            last_write_cycle: None,
+            chr_a12: false,
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x8000..=0x9FFF => self.control = value,
+            0xA000..=0xBFFF => self.chr_bank_0 = value,
+            0xC000..=0xDFFF => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+
+    // the chr bank register currently driving the upper address lines on SxROM boards
+    fn outer_bank(&self) -> u8 {
+        if self.control & 0x10 != 0 && self.chr_a12 {
+            return self.chr_bank_1;
+        }
+
+        return self.chr_bank_0;
+    }
+
+    fn prg_rom_bank(&self, address: u16) -> usize {
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let high = address >= 0xC000;
+
+        let bank = match (self.control >> 2) & 0x3 {
+            0 | 1 => (bank & 0x0E) | high as usize,
+            2 => if high { bank } else { 0 },
+            _ => if high { 0x0F } else { bank },
+        };
+
+        // SUROM and SXROM, 16 banks per 256KB half
+        if self.cartridge.prg_rom.len() > 0x40000 {
+            return bank | (self.outer_bank() & 0x10) as usize;
+        }
+
+        return bank;
+    }
+
+    fn prg_ram_bank(&self) -> usize {
+        match self.cartridge.prg_ram.len() {
+            0x4000 => ((self.outer_bank() >> 3) & 0x1) as usize, // SOROM
+            0x8000 => ((self.outer_bank() >> 2) & 0x3) as usize, // SXROM
+            _ => 0,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        if self.control & 0x10 == 0 {
+            return (self.chr_bank_0 & 0x1E) as usize | (address >> 12) as usize;
+        }
+
+        if address < 0x1000 {
+            return self.chr_bank_0 as usize;
+        }
+        return self.chr_bank_1 as usize;
+    }
+}
+
+impl mapper::Mapper for MMC1 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => {
+                if self.prg_bank & 0x10 != 0 {
+                    return None;
+                }
+                return self.cartridge.read_prg_ram(self.prg_ram_bank() * 0x2000 + (address as usize - 0x6000));
+            },
+            0x8000..=0xFFFF => Some(self.cartridge.read_prg(0x4000, self.prg_rom_bank(address), address as usize & 0x3FFF)),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x6000..=0x7FFF => {
+                if self.prg_bank & 0x10 == 0 {
+                    let offset = self.prg_ram_bank() * 0x2000 + (address as usize - 0x6000);
+                    self.cartridge.write_prg_ram(offset, value);
+                }
+            },
+            0x8000..=0xFFFF => {
+                // the serial port ignores the second of two writes on consecutive cycles, like the ones
+                // read-modify-write instructions do
+                let consecutive = self.last_write_cycle.map_or(false, |cycle| self.cycle - cycle <= 1);
+                self.last_write_cycle = Some(self.cycle);
+                if consecutive {
+                    return;
+                }
+
+                if value & 0x80 != 0 {
+                    self.shift = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                self.shift |= (value & 0x1) << self.shift_count;
+                self.shift_count += 1;
+
+                if self.shift_count == 5 {
+                    self.write_register(address, self.shift);
+                    self.shift = 0;
+                    self.shift_count = 0;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr_a12 = address & 0x1000 != 0;
+        return self.cartridge.read_chr(0x1000, self.chr_bank(address), address as usize & 0x0FFF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_bank(address);
+        self.cartridge.write_chr(0x1000, bank, address as usize & 0x0FFF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        match self.control & 0x3 {
+            0 => ppu::Mirroring::SingleScreenA,
+            1 => ppu::Mirroring::SingleScreenB,
+            2 => ppu::Mirroring::Vertical,
+            _ => ppu::Mirroring::Horizontal,
+        }
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
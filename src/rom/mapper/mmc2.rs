@@ -0,0 +1,122 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    MMC2 (mapper 9) and MMC4 (mapper 10), see https://www.nesdev.org/wiki/MMC2 and https://www.nesdev.org/wiki/MMC4
+
+    $A000-$AFFF     prg bank at $8000, 8KB on MMC2 with the last 3 banks fixed, 16KB on MMC4 with the last one fixed
+    $B000-$BFFF     4KB chr bank at $0000 when latch 0 is $FD
+    $C000-$CFFF     4KB chr bank at $0000 when latch 0 is $FE
+    $D000-$DFFF     4KB chr bank at $1000 when latch 1 is $FD
+    $E000-$EFFF     4KB chr bank at $1000 when latch 1 is $FE
+    $F000-$FFFF     mirroring (0: vertical; 1: horizontal)
+
+    the latches are set by the ppu fetching tiles $FD and $FE, on the high byte fetch which reads
+    $xFD8-$xFDF or $xFE8-$xFEF. The byte that triggers it still comes from the old bank, the new one is used
+    from the next fetch on. MMC2 only watches $0FD8/$0FE8 exactly for latch 0, MMC4 watches the whole range.
+*/
+
+#[derive(Clone)]
+pub struct MMC2 {
+    pub cartridge: mapper::Cartridge,
+    pub mmc4: bool,
+    pub prg_bank: usize,
+    pub chr_banks: [[usize; 2]; 2], // [pattern table][latch $FD, latch $FE]
+    pub latches: [usize; 2], // 0 for $FD, 1 for $FE
+    pub mirroring: ppu::Mirroring,
+}
+
+impl MMC2 {
+    pub fn new(rom: &rom::ROM, mmc4: bool) -> MMC2 {
+        MMC2 {
+            cartridge: mapper::Cartridge::new(rom),
+            mmc4: mmc4,
+            prg_bank: 0,
+            chr_banks: [[0, 0], [0, 0]],
+            latches: [1, 1],
+            mirroring: rom.header.mirroring(),
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        let table = (address >> 12) as usize & 0x1;
+        return self.chr_banks[table][self.latches[table]];
+    }
+
+    fn update_latches(&mut self, address: u16) {
+        let table = (address >> 12) as usize & 0x1;
+        let exact = table == 0 && !self.mmc4;
+
+        match address & 0x0FF8 {
+            0x0FD8 if !exact || address & 0x7 == 0 => self.latches[table] = 0,
+            0x0FE8 if !exact || address & 0x7 == 0 => self.latches[table] = 1,
+            _ => {}
+        }
+    }
+}
+
+impl mapper::Mapper for MMC2 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF if self.mmc4 => self.cartridge.read_prg_ram(address as usize - 0x6000),
+            0x8000..=0xFFFF if self.mmc4 => {
+                let bank = if address < 0xC000 { self.prg_bank } else { self.cartridge.prg_banks(0x4000) - 1 };
+                return Some(self.cartridge.read_prg(0x4000, bank, address as usize & 0x3FFF));
+            },
+            0x8000..=0xFFFF => {
+                let last = self.cartridge.prg_banks(0x2000);
+                let bank = match address {
+                    0x8000..=0x9FFF => self.prg_bank,
+                    _ => last.saturating_sub(4) + ((address as usize - 0x8000) >> 13),
+                };
+                return Some(self.cartridge.read_prg(0x2000, bank, address as usize & 0x1FFF));
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        let value = value as usize;
+
+        match address {
+            0x6000..=0x7FFF if self.mmc4 => self.cartridge.write_prg_ram(address as usize - 0x6000, value as u8),
+            0xA000..=0xAFFF => self.prg_bank = value & 0x0F,
+            0xB000..=0xBFFF => self.chr_banks[0][0] = value & 0x1F,
+            0xC000..=0xCFFF => self.chr_banks[0][1] = value & 0x1F,
+            0xD000..=0xDFFF => self.chr_banks[1][0] = value & 0x1F,
+            0xE000..=0xEFFF => self.chr_banks[1][1] = value & 0x1F,
+            0xF000..=0xFFFF => {
+                self.mirroring = if value & 0x1 != 0 { ppu::Mirroring::Horizontal } else { ppu::Mirroring::Vertical };
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let value = self.cartridge.read_chr(0x1000, self.chr_bank(address), address as usize & 0x0FFF);
+        self.update_latches(address);
+        return value;
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_bank(address);
+        self.cartridge.write_chr(0x1000, bank, address as usize & 0x0FFF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.mirroring;
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        if !self.mmc4 {
+            return None;
+        }
+
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    MMC3 and MMC6 (mapper 4), see https://www.nesdev.org/wiki/MMC3 and https://www.nesdev.org/wiki/MMC6
+
+    $8000 even      bank select
+    $8001 odd       bank data
+    $A000 even      mirroring (0: vertical; 1: horizontal)
+    $A001 odd       prg ram protect
+    $C000 even      irq latch
+    $C001 odd       irq reload
+    $E000 even      irq disable and acknowledge
+    $E001 odd       irq enable
+
+    bank select
+    76543210
+    ||   +++- bank register to update on the next $8001 write (R0-R7)
+    |+------- prg mode (0: $8000 swappable, $C000 fixed to second last bank; 1: the other way round)
+    +-------- chr a12 inversion (0: two 2KB banks at $0000, four 1KB banks at $1000; 1: the other way round)
+
+    the irq counter is clocked by rising edges of ppu a12, filtered so only edges after a12 has been
+    low for a while count. With the usual setup of background at $0000 and sprites at $1000 that's once per line.
+
+    the revisions differ in when the irq fires, Sharp chips fire whenever the counter is 0 after being
+    clocked, NEC MMC3A chips only when it's decremented or reloaded to 0. The revision comes from the
+    NES 2.0 submapper unless --mmc3 picks one.
+
+    plain iNES files can't tell MMC6 and boards without the ram protect apart from MMC3, so for them
+    the prg ram is always enabled and writable and $A001 is ignored.
+
+    MMC6 has 1KB of ram inside the mapper at $7000-$7FFF, two 512 byte halves with their own read and
+    write enables in $A001, all of it gated by bit 5 of bank select.
+*/
+
+// how many ppu dots a12 has to stay low before a rising edge clocks the counter, about 3 cpu cycles
+static A12_FILTER_DOTS: u64 = 10;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Revision {
+    Sharp,
+    NEC,
+    MMC6,
+}
+
+impl Revision {
+    pub fn from_name(name: &str) -> Option<Revision> {
+        match name {
+            "sharp" => Some(Revision::Sharp),
+            "nec" => Some(Revision::NEC),
+            "mmc6" => Some(Revision::MMC6),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MMC3 {
+    pub cartridge: mapper::Cartridge,
+    pub revision: Revision,
+    pub bank_select: u8,
+    pub registers: [u8; 8],
+    pub mirroring: ppu::Mirroring,
+    pub prg_ram_protect: u8,
+    pub ignore_ram_protect: bool, // plain iNES files, see the top
+    pub mmc6_ram: [u8; 0x400],
+
+    pub irq_latch: u8,
+    pub irq_counter: u8,
+    pub irq_reload: bool,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+
+    pub a12: bool,
+    pub a12_low_dot: u64, // dot a12 last went low
+    pub dot: u64,
+}
+
+impl MMC3 {
+    pub fn new(rom: &rom::ROM) -> MMC3 {
+        let submapper = if rom.header.nes_2 { rom.header.submapper } else { 0 };
+        let revision = rom.mmc3_revision.unwrap_or(match submapper {
+            1 => Revision::MMC6,
+            4 => Revision::NEC,
+            _ => Revision::Sharp,
+        });
+
+        MMC3 {
+            cartridge: mapper::Cartridge::new(rom),
+            revision: revision,
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: rom.header.mirroring(),
+            prg_ram_protect: 0,
+            ignore_ram_protect: !rom.header.nes_2,
+            mmc6_ram: [0; 0x400],
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+
+            a12: false,
+            a12_low_dot: 0,
+            dot: 0,
+        }
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        let second_last = self.cartridge.prg_banks(0x2000).saturating_sub(2);
+        let swapped = self.bank_select & 0x40 != 0;
+
+        match (address >> 13) & 0x3 {
+            0 => if swapped { second_last } else { self.registers[6] as usize },
+            1 => self.registers[7] as usize,
+            2 => if swapped { self.registers[6] as usize } else { second_last },
+            _ => second_last + 1,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        // inversion swaps which pattern table gets the 2KB banks
+        let address = if self.bank_select & 0x80 != 0 { address ^ 0x1000 } else { address };
+        let slot = (address >> 10) as usize;
+
+        match slot {
+            0..=3 => (self.registers[slot >> 1] & 0xFE) as usize + (slot & 0x1),
+            _ => self.registers[slot - 2] as usize,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        let fire = match self.revision {
+            Revision::NEC => self.irq_counter == 0 && (previous != 0 || reloaded),
+            _ => self.irq_counter == 0,
+        };
+
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn watch_a12(&mut self, address: u16) {
+        let a12 = address & 0x1000 != 0;
+
+        if a12 && !self.a12 && self.dot - self.a12_low_dot >= A12_FILTER_DOTS {
+            self.clock_irq_counter();
+        }
+
+        if !a12 && self.a12 {
+            self.a12_low_dot = self.dot;
+        }
+        self.a12 = a12;
+    }
+
+    // MMC6 ram, None when the half being read is disabled
+    fn read_mmc6_ram(&self, address: u16) -> Option<u8> {
+        let protect = self.prg_ram_protect;
+        if self.bank_select & 0x20 == 0 || protect & 0xA0 == 0 {
+            return None;
+        }
+
+        let high_half = address & 0x200 != 0;
+        let readable = if high_half { protect & 0x80 != 0 } else { protect & 0x20 != 0 };
+
+        // with only one half readable, the other reads as 0
+        if !readable {
+            return Some(0);
+        }
+        return Some(self.mmc6_ram[address as usize & 0x3FF]);
+    }
+
+    fn write_mmc6_ram(&mut self, address: u16, value: u8) {
+        let high_half = address & 0x200 != 0;
+        let writable = if high_half { self.prg_ram_protect & 0x40 != 0 } else { self.prg_ram_protect & 0x10 != 0 };
+
+        if self.bank_select & 0x20 != 0 && writable {
+            self.mmc6_ram[address as usize & 0x3FF] = value;
+        }
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        let odd = address & 0x1 != 0;
+
+        match (address & 0xE000, odd) {
+            (0x8000, false) => {
+                // clearing bit 5 turns the MMC6 ram off, along with all of its enables
+                if self.revision == Revision::MMC6 && value & 0x20 == 0 {
+                    self.prg_ram_protect = 0;
+                }
+                self.bank_select = value;
+            },
+            (0x8000, true) => self.registers[(self.bank_select & 0x7) as usize] = value,
+            (0xA000, false) => {
+                if self.mirroring != ppu::Mirroring::FourScreen {
+                    self.mirroring = if value & 0x1 != 0 { ppu::Mirroring::Horizontal } else { ppu::Mirroring::Vertical };
+                }
+            },
+            (0xA000, true) => {
+                if self.revision != Revision::MMC6 || self.bank_select & 0x20 != 0 {
+                    self.prg_ram_protect = value;
+                }
+            },
+            (0xC000, false) => self.irq_latch = value,
+            (0xC000, true) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            },
+            (0xE000, false) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            },
+            _ => self.irq_enabled = true,
+        }
+    }
+}
+
+impl mapper::Mapper for MMC3 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x7000..=0x7FFF if self.revision == Revision::MMC6 => self.read_mmc6_ram(address),
+            0x6000..=0x7FFF => {
+                // bit 7 enables the ram
+                if self.revision == Revision::MMC6 || (!self.ignore_ram_protect && self.prg_ram_protect & 0x80 == 0) {
+                    return None;
+                }
+                return self.cartridge.read_prg_ram(address as usize - 0x6000);
+            },
+            0x8000..=0xFFFF => Some(self.cartridge.read_prg(0x2000, self.prg_bank(address), address as usize & 0x1FFF)),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x7000..=0x7FFF if self.revision == Revision::MMC6 => self.write_mmc6_ram(address, value),
+            0x6000..=0x7FFF => {
+                // bit 6 write protects it
+                let writable = self.ignore_ram_protect || self.prg_ram_protect & 0xC0 == 0x80;
+                if self.revision != Revision::MMC6 && writable {
+                    self.cartridge.write_prg_ram(address as usize - 0x6000, value);
+                }
+            },
+            0x8000..=0xFFFF => self.write_register(address, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.watch_a12(address);
+        return self.cartridge.read_chr(0x400, self.chr_bank(address), address as usize & 0x3FF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.watch_a12(address);
+        let bank = self.chr_bank(address);
+        self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.mirroring;
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn ppu_cycle(&mut self, _scanline: u32, _cycle: u32) {
+        self.dot += 1;
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        if self.revision == Revision::MMC6 {
+            return if self.cartridge.battery { Some(self.mmc6_ram.to_vec()) } else { None };
+        }
+
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        if self.revision == Revision::MMC6 {
+            let len = data.len().min(self.mmc6_ram.len());
+            self.mmc6_ram[..len].copy_from_slice(&data[..len]);
+            return;
+        }
+
+        self.cartridge.load_save_ram(data);
+    }
+}
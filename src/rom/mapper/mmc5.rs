@@ -0,0 +1,456 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    MMC5 (mapper 5), see https://www.nesdev.org/wiki/MMC5
+
+    $5100           prg mode (0: 32KB; 1: 16KB+16KB; 2: 16KB+8KB+8KB; 3: 8KB x4)
+    $5101           chr mode (0: 8KB; 1: 4KB; 2: 2KB; 3: 1KB)
+    $5102/$5103     prg ram write enable, only when they hold 2 and 1
+    $5104           exram mode (0: nametable; 1: extended attributes; 2: cpu ram; 3: cpu read only ram)
+    $5105           nametable mapping, 2 bits per nametable (0: ciram page 0; 1: ciram page 1; 2: exram; 3: fill mode)
+    $5106/$5107     fill mode tile and palette
+    $5113           8KB prg ram bank at $6000
+    $5114-$5117     prg banks, bit 7 picks rom (1) or ram (0), $5117 is always rom
+    $5120-$5127     chr banks for sprites, or everything with 8x8 sprites
+    $5128-$512B     chr banks for the background with 8x16 sprites, unused with 8x8 sprites
+    $5130           upper chr bank bits
+    $5200-$5202     vertical split control, scroll and chr bank
+    $5203           irq scanline compare
+    $5204           irq enable (write), irq pending and in frame status (read)
+    $5205/$5206     8x8 unsigned multiplier
+    $5C00-$5FFF     exram
+
+    the MMC5 has no scanline counter pin, it works out where the ppu is from the fetches it sees. A new
+    line starts with three reads of the same nametable address (two dummy fetches at the end of the line then
+    the first real one), and the frame is over once the fetches stop. Fetches in dots 257-320 are sprites.
+    It also snoops $2000 and $2001 for the sprite size and rendering enable.
+
+    extended attributes give every background tile its own 4KB chr bank and palette, from the exram byte
+    at the same index as the tile
+    76543210
+    ||++++++- 4KB chr bank
+    ++------- palette
+*/
+
+#[derive(Clone)]
+pub struct MMC5 {
+    pub cartridge: mapper::Cartridge,
+    pub exram: [u8; 0x400],
+
+    pub prg_mode: u8,
+    pub chr_mode: u8,
+    pub prg_ram_protect: [u8; 2],
+    pub exram_mode: u8,
+    pub nametable_mapping: u8,
+    pub fill_tile: u8,
+    pub fill_palette: u8,
+    pub prg_banks: [u8; 5], // $5113-$5117
+    pub sprite_chr_banks: [usize; 8],
+    pub background_chr_banks: [usize; 4],
+    pub chr_upper: usize,
+    pub last_chr_write_background: bool,
+
+    pub split_control: u8,
+    pub split_scroll: u8,
+    pub split_bank: usize,
+    pub in_split: bool,
+    pub split_tile: u8,
+    pub split_y: usize,
+
+    pub irq_compare: u8,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+    pub in_frame: bool,
+    pub scanline_counter: u8,
+
+    pub multiplicand: u8,
+    pub multiplier: u8,
+
+    // ppu snooping
+    pub large_sprites: bool,
+    pub rendering: bool,
+    pub scanline: u32,
+    pub cycle: u32,
+    pub idle_dots: u32,
+    pub last_nametable_address: u16,
+    pub nametable_repeats: u8,
+    pub ext_attribute: u8, // exram byte for the tile being fetched
+}
+
+impl MMC5 {
+    pub fn new(rom: &rom::ROM) -> MMC5 {
+        MMC5 {
+            cartridge: mapper::Cartridge::new(rom),
+            exram: [0; 0x400],
+
+            prg_mode: 3,
+            chr_mode: 0,
+            prg_ram_protect: [0; 2],
+            exram_mode: 0,
+            nametable_mapping: 0,
+            fill_tile: 0,
+            fill_palette: 0,
+            prg_banks: [0, 0xFF, 0xFF, 0xFF, 0xFF],
+            sprite_chr_banks: [0; 8],
+            background_chr_banks: [0; 4],
+            chr_upper: 0,
+            last_chr_write_background: false,
+
+            split_control: 0,
+            split_scroll: 0,
+            split_bank: 0,
+            in_split: false,
+            split_tile: 0,
+            split_y: 0,
+
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline_counter: 0,
+
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+
+            large_sprites: false,
+            rendering: false,
+            scanline: 0,
+            cycle: 0,
+            idle_dots: 0,
+            last_nametable_address: 0,
+            nametable_repeats: 0,
+            ext_attribute: 0,
+        }
+    }
+
+    // returns the bank register and the window size in 8KB banks for a $8000-$FFFF address
+    fn prg_register(&self, address: u16) -> (u8, usize) {
+        let window = ((address - 0x8000) >> 13) as usize;
+
+        match self.prg_mode & 0x3 {
+            0 => (self.prg_banks[4], 4),
+            1 => (self.prg_banks[if window < 2 { 2 } else { 4 }], 2),
+            2 => match window {
+                0 | 1 => (self.prg_banks[2], 2),
+                2 => (self.prg_banks[3], 1),
+                _ => (self.prg_banks[4], 1),
+            },
+            _ => (self.prg_banks[1 + window], 1),
+        }
+    }
+
+    // (rom, 8KB bank) for a $8000-$FFFF address
+    fn prg_bank(&self, address: u16) -> (bool, usize) {
+        let (register, size) = self.prg_register(address);
+        let rom = register & 0x80 != 0 || address >= 0xE000;
+        let bank = (register & 0x7F) as usize & !(size - 1);
+        let window = ((address - 0x8000) >> 13) as usize;
+
+        return (rom, bank + (window & (size - 1)));
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        return self.prg_ram_protect[0] & 0x3 == 0x2 && self.prg_ram_protect[1] & 0x3 == 0x1;
+    }
+
+    fn sprite_fetch(&self) -> bool {
+        return self.in_frame && (257..=320).contains(&self.cycle);
+    }
+
+    // 1KB chr bank for an address, from the sprite or background set
+    fn chr_bank(&self, address: u16, background: bool) -> usize {
+        let slot = (address >> 10) as usize & 0x7;
+
+        if background {
+            let banks = &self.background_chr_banks;
+            return match self.chr_mode & 0x3 {
+                0 => banks[3] * 8 + slot,
+                1 => banks[3] * 4 + (slot & 0x3),
+                2 => banks[(slot & 0x2) | 0x1] * 2 + (slot & 0x1),
+                _ => banks[slot & 0x3],
+            };
+        }
+
+        let banks = &self.sprite_chr_banks;
+        return match self.chr_mode & 0x3 {
+            0 => banks[7] * 8 + slot,
+            1 => banks[(slot & 0x4) | 0x3] * 4 + (slot & 0x3),
+            2 => banks[(slot & 0x6) | 0x1] * 2 + (slot & 0x1),
+            _ => banks[slot],
+        };
+    }
+
+    // with 8x8 sprites only $5120-$5127 are used. With 8x16 sprites the background has its own banks while
+    // rendering, and $2007 accesses outside of it use whichever set was written last
+    fn use_background_banks(&self) -> bool {
+        if !self.large_sprites {
+            return false;
+        }
+
+        if self.rendering && self.in_frame {
+            return !self.sprite_fetch();
+        }
+
+        return self.last_chr_write_background;
+    }
+
+    // a new line was detected, see the top
+    fn start_scanline(&mut self) {
+        if !self.in_frame {
+            self.in_frame = true;
+            self.scanline_counter = 0;
+            return;
+        }
+
+        self.scanline_counter = self.scanline_counter.wrapping_add(1);
+        if self.scanline_counter == self.irq_compare {
+            self.irq_pending = true;
+        }
+    }
+
+    // tile column and line the background fetch at the current dot is for, dots 321-336 prefetch the next line
+    fn fetch_position(&self) -> (usize, usize) {
+        if self.cycle >= 321 {
+            let line = if self.scanline == 261 { 0 } else { self.scanline as usize + 1 };
+            return (((self.cycle - 321) / 8) as usize, line);
+        }
+
+        return (((self.cycle.max(1) - 1) / 8) as usize + 2, self.scanline as usize);
+    }
+
+    fn background_fetch(&self) -> bool {
+        return self.in_frame && ((1..=256).contains(&self.cycle) || (321..=340).contains(&self.cycle));
+    }
+
+    // the split replaces background fetches on one side of the screen with its own nametable from exram
+    fn split_read(&mut self, address: u16) -> Option<u8> {
+        if self.split_control & 0x80 == 0 || self.exram_mode > 1 || !self.background_fetch() {
+            self.in_split = false;
+            return None;
+        }
+
+        let (tile_x, line) = self.fetch_position();
+        let threshold = (self.split_control & 0x1F) as usize;
+        let right_side = self.split_control & 0x40 != 0;
+
+        if tile_x >= 32 || (right_side && tile_x < threshold) || (!right_side && tile_x >= threshold) {
+            self.in_split = false;
+            return None;
+        }
+
+        self.in_split = true;
+        self.split_y = (line + self.split_scroll as usize) % 240;
+        let coarse_y = self.split_y / 8;
+
+        if address & 0x3FF < 0x3C0 {
+            self.split_tile = self.exram[coarse_y * 32 + tile_x];
+            return Some(self.split_tile);
+        }
+
+        let attribute = self.exram[0x3C0 + (coarse_y / 4) * 8 + tile_x / 4];
+        let shift = ((coarse_y & 0x2) << 1) | (tile_x & 0x2);
+        return Some(((attribute >> shift) & 0x3) * 0x55);
+    }
+
+    fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x5100 => self.prg_mode = value & 0x3,
+            0x5101 => self.chr_mode = value & 0x3,
+            0x5102 => self.prg_ram_protect[0] = value,
+            0x5103 => self.prg_ram_protect[1] = value,
+            0x5104 => self.exram_mode = value & 0x3,
+            0x5105 => self.nametable_mapping = value,
+            0x5106 => self.fill_tile = value,
+            0x5107 => self.fill_palette = value & 0x3,
+            0x5113..=0x5117 => self.prg_banks[(address - 0x5113) as usize] = value,
+            0x5120..=0x5127 => {
+                self.sprite_chr_banks[(address - 0x5120) as usize] = value as usize | (self.chr_upper << 8);
+                self.last_chr_write_background = false;
+            },
+            0x5128..=0x512B => {
+                self.background_chr_banks[(address - 0x5128) as usize] = value as usize | (self.chr_upper << 8);
+                self.last_chr_write_background = true;
+            },
+            0x5130 => self.chr_upper = (value & 0x3) as usize,
+            0x5200 => self.split_control = value,
+            0x5201 => self.split_scroll = value,
+            0x5202 => self.split_bank = value as usize,
+            0x5203 => self.irq_compare = value,
+            0x5204 => self.irq_enabled = value & 0x80 != 0,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF => {
+                // in the nametable modes exram can only be written while rendering, otherwise 0 goes in
+                match self.exram_mode {
+                    0 | 1 => self.exram[address as usize - 0x5C00] = if self.in_frame { value } else { 0 },
+                    2 => self.exram[address as usize - 0x5C00] = value,
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+impl mapper::Mapper for MMC5 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x5204 => {
+                let status = ((self.irq_pending as u8) << 7) | ((self.in_frame as u8) << 6);
+                self.irq_pending = false;
+                return Some(status);
+            },
+            0x5205 => Some(((self.multiplicand as u16 * self.multiplier as u16) & 0xFF) as u8),
+            0x5206 => Some(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Some(self.exram[address as usize - 0x5C00]),
+            0x6000..=0x7FFF => {
+                let bank = (self.prg_banks[0] & 0x7) as usize;
+                return self.cartridge.read_prg_ram(bank * 0x2000 + (address as usize & 0x1FFF));
+            },
+            0x8000..=0xFFFF => {
+                let (rom, bank) = self.prg_bank(address);
+                if rom {
+                    return Some(self.cartridge.read_prg(0x2000, bank, address as usize & 0x1FFF));
+                }
+                return self.cartridge.read_prg_ram((bank & 0x7) * 0x2000 + (address as usize & 0x1FFF));
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x5000..=0x5FFF => self.write_register(address, value),
+            0x6000..=0x7FFF => {
+                if self.prg_ram_writable() {
+                    let bank = (self.prg_banks[0] & 0x7) as usize;
+                    self.cartridge.write_prg_ram(bank * 0x2000 + (address as usize & 0x1FFF), value);
+                }
+            },
+            0x8000..=0xDFFF => {
+                let (rom, bank) = self.prg_bank(address);
+                if !rom && self.prg_ram_writable() {
+                    self.cartridge.write_prg_ram((bank & 0x7) * 0x2000 + (address as usize & 0x1FFF), value);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.idle_dots = 0;
+        let sprite = self.sprite_fetch();
+
+        if !sprite && self.in_split {
+            let offset = ((self.split_tile as usize) << 4) | (address as usize & 0x8) | (self.split_y & 0x7);
+            return self.cartridge.read_chr(0x1000, self.split_bank, offset);
+        }
+
+        if !sprite && self.exram_mode == 1 && self.background_fetch() {
+            let bank = (self.ext_attribute & 0x3F) as usize | (self.chr_upper << 6);
+            return self.cartridge.read_chr(0x1000, bank, address as usize & 0x0FFF);
+        }
+
+        let bank = self.chr_bank(address, self.use_background_banks());
+        return self.cartridge.read_chr(0x400, bank, address as usize & 0x3FF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_bank(address, self.use_background_banks());
+        self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        let mapping = self.nametable_mapping as usize;
+        return ppu::Mirroring::Mapped([mapping & 0x1, (mapping >> 2) & 0x1, (mapping >> 4) & 0x1, (mapping >> 6) & 0x1]);
+    }
+
+    fn nametable_read(&mut self, address: u16) -> Option<u8> {
+        self.idle_dots = 0;
+
+        if address == self.last_nametable_address {
+            self.nametable_repeats += 1;
+            if self.nametable_repeats == 2 {
+                self.start_scanline();
+            }
+        } else {
+            self.last_nametable_address = address;
+            self.nametable_repeats = 0;
+        }
+
+        if let Some(value) = self.split_read(address) {
+            return Some(value);
+        }
+
+        let offset = address as usize & 0x3FF;
+        let attribute = offset >= 0x3C0;
+
+        // extended attributes replace the attribute byte of every tile, whichever nametable it's in
+        if self.exram_mode == 1 && self.background_fetch() {
+            if !attribute {
+                self.ext_attribute = self.exram[offset];
+            } else {
+                return Some((self.ext_attribute >> 6) * 0x55);
+            }
+        }
+
+        match (self.nametable_mapping >> (((address >> 10) & 0x3) * 2)) & 0x3 {
+            2 => Some(if self.exram_mode <= 1 { self.exram[offset] } else { 0 }),
+            3 => Some(if attribute { self.fill_palette * 0x55 } else { self.fill_tile }),
+            _ => None,
+        }
+    }
+
+    fn nametable_write(&mut self, address: u16, value: u8) -> bool {
+        match (self.nametable_mapping >> (((address >> 10) & 0x3) * 2)) & 0x3 {
+            2 => {
+                if self.exram_mode <= 1 {
+                    self.exram[address as usize & 0x3FF] = value;
+                }
+                return true;
+            },
+            3 => return true,
+            _ => return false,
+        }
+    }
+
+    fn ppu_register_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x2000 => self.large_sprites = value & 0x20 != 0,
+            0x2001 => {
+                self.rendering = value & 0x18 != 0;
+                if !self.rendering {
+                    self.in_frame = false;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.irq_pending && self.irq_enabled;
+    }
+
+    // the frame ends when the ppu stops fetching, a few cpu cycles without any reads
+    fn ppu_cycle(&mut self, scanline: u32, cycle: u32) {
+        self.scanline = scanline;
+        self.cycle = cycle;
+
+        self.idle_dots += 1;
+        if self.idle_dots > 8 {
+            self.in_frame = false;
+            self.last_nametable_address = 0;
+            self.nametable_repeats = 0;
+        }
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+use crate::ppu;
+use crate::rom;
+mod nrom;
+mod mmc1;
+pub mod mmc3;
+mod discrete;
+mod mmc2;
+mod mmc5;
+mod vrc2;
+mod fme7;
+mod namco163;
+
+/*
+    mappers decide what the cpu and ppu see in cartridge space and how writes to it switch banks, see https://www.nesdev.org/wiki/Mapper
+
+    cpu $4020-$FFFF     prg rom, prg ram and mapper registers
+    ppu $0000-$1FFF     chr rom or chr ram (pattern tables), or ciram on mappers that can put it there
+    ppu $2000-$2FFF     nametables, the console's ciram unless the mapper takes them over
+
+    every pattern and nametable fetch the renderer makes goes through the mapper, so mappers that watch
+    the ppu address bus can do it from there.
+*/
+
+// lets a boxed mapper be copied for save states, every mapper gets it by deriving Clone
+pub trait MapperClone {
+    fn clone_box(&self) -> Box<dyn Mapper>;
+}
+
+impl<T: 'static + Mapper + Clone> MapperClone for T {
+    fn clone_box(&self) -> Box<dyn Mapper> {
+        return Box::new(self.clone());
+    }
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Box<dyn Mapper> {
+        return self.clone_box();
+    }
+}
+
+pub trait Mapper: MapperClone {
+    // None leaves the data bus floating
+    fn cpu_read(&mut self, address: u16) -> Option<u8>;
+    fn cpu_write(&mut self, address: u16, value: u8);
+
+    fn ppu_read(&mut self, address: u16) -> u8;
+    fn ppu_write(&mut self, address: u16, value: u8);
+
+    // how the nametables are currently mirrored
+    fn mirroring(&self) -> ppu::Mirroring;
+
+    // nametable accesses, address is $2000-$2FFF. None or false leaves them to ciram
+    fn nametable_read(&mut self, _address: u16) -> Option<u8> {
+        return None;
+    }
+
+    fn nametable_write(&mut self, _address: u16, _value: u8) -> bool {
+        return false;
+    }
+
+    // ciram page (0 or 1) a pattern table address is mapped to, None for chr like usual
+    fn chr_ciram_page(&self, _address: u16) -> Option<usize> {
+        return None;
+    }
+
+    // cpu writes to the ppu registers $2000-$2007, for mappers that snoop them
+    fn ppu_register_write(&mut self, _address: u16, _value: u8) {}
+
+    fn irq_pending(&self) -> bool {
+        return false;
+    }
+
+    // called once per cpu cycle, for mappers that count cycles
+    fn cpu_cycle(&mut self) {}
+
+    // called once per ppu dot, before the dot at scanline/cycle is run
+    fn ppu_cycle(&mut self, _scanline: u32, _cycle: u32) {}
+
+    // battery backed prg ram, None if the board doesn't have any
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return None;
+    }
+
+    fn load_save_ram(&mut self, _data: &[u8]) {}
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedMapper(number) => write!(f, "mapper {} is not supported", number),
+        }
+    }
+}
+
+// format is (mapper number, constructor)
+pub static MAPPERS: [(u16, fn(&rom::ROM) -> Box<dyn Mapper>); 18] = [
+    (0, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(nrom::NROM::new(rom)) }),
+    (1, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(mmc1::MMC1::new(rom)) }),
+    (2, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new(rom, discrete::Board::UxROM)) }),
+    (3, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new(rom, discrete::Board::CNROM)) }),
+    (4, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(mmc3::MMC3::new(rom)) }),
+    (5, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(mmc5::MMC5::new(rom)) }),
+    (7, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new(rom, discrete::Board::AxROM)) }),
+    (9, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(mmc2::MMC2::new(rom, false)) }),
+    (10, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(mmc2::MMC2::new(rom, true)) }),
+    (11, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new(rom, discrete::Board::ColorDreams)) }),
+    (19, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(namco163::Namco163::new(rom)) }),
+    (21, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(vrc2::VRC2::new(rom)) }),
+    (22, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(vrc2::VRC2::new(rom)) }),
+    (23, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(vrc2::VRC2::new(rom)) }),
+    (25, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(vrc2::VRC2::new(rom)) }),
+    (34, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new_34(rom)) }),
+    (66, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(discrete::Discrete::new(rom, discrete::Board::GxROM)) }),
+    (69, |rom: &rom::ROM| -> Box<dyn Mapper> { Box::new(fme7::FME7::new(rom)) }),
+];
+
+pub fn new(rom: &rom::ROM) -> Result<Box<dyn Mapper>, Error> {
+    match MAPPERS.iter().find(|&x| x.0 == rom.header.mapper) {
+        Some(mapper) => Ok(mapper.1(rom)),
+        None => Err(Error::UnsupportedMapper(rom.header.mapper)),
+    }
+}
+
+// the memory every board has, mappers pick which banks of it are visible
+#[derive(Clone)]
+pub struct Cartridge {
+    pub prg_rom: Vec<u8>,
+    pub prg_ram: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub chr_is_ram: bool, // boards without chr rom have 8KB of chr ram instead
+    pub battery: bool,
+    pub mirroring: ppu::Mirroring,
+}
+
+impl Cartridge {
+    pub fn new(rom: &rom::ROM) -> Cartridge {
+        let chr_is_ram = rom.chr_rom.is_empty();
+
+        Cartridge {
+            prg_rom: rom.prg_rom.clone(),
+            prg_ram: vec![0; rom.header.prg_ram_bytes()],
+            chr: if chr_is_ram { vec![0; rom.header.chr_ram_bytes()] } else { rom.chr_rom.clone() },
+            chr_is_ram: chr_is_ram,
+            battery: rom.header.persistent_memory,
+            mirroring: rom.header.mirroring(),
+        }
+    }
+
+    pub fn prg_banks(&self, bank_size: usize) -> usize {
+        return (self.prg_rom.len() / bank_size).max(1);
+    }
+
+    pub fn chr_banks(&self, bank_size: usize) -> usize {
+        return (self.chr.len() / bank_size).max(1);
+    }
+
+    // bank numbers past the end of the rom wrap around, like the unconnected upper bank lines would
+    pub fn read_prg(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        let index = (bank % self.prg_banks(bank_size)) * bank_size + (offset % bank_size);
+        return self.prg_rom[index % self.prg_rom.len()];
+    }
+
+    pub fn read_chr(&self, bank_size: usize, bank: usize, offset: usize) -> u8 {
+        if self.chr.is_empty() {
+            return 0;
+        }
+
+        let index = (bank % self.chr_banks(bank_size)) * bank_size + (offset % bank_size);
+        return self.chr[index % self.chr.len()];
+    }
+
+    // writes to chr rom are ignored
+    pub fn write_chr(&mut self, bank_size: usize, bank: usize, offset: usize, value: u8) {
+        if !self.chr_is_ram || self.chr.is_empty() {
+            return;
+        }
+
+        let index = (bank % self.chr_banks(bank_size)) * bank_size + (offset % bank_size);
+        let len = self.chr.len();
+        self.chr[index % len] = value;
+    }
+
+    // offset is from $6000, ram smaller than the window is mirrored through it
+    pub fn read_prg_ram(&self, offset: usize) -> Option<u8> {
+        if self.prg_ram.is_empty() {
+            return None;
+        }
+
+        return Some(self.prg_ram[offset % self.prg_ram.len()]);
+    }
+
+    pub fn write_prg_ram(&mut self, offset: usize, value: u8) {
+        if self.prg_ram.is_empty() {
+            return;
+        }
+
+        let len = self.prg_ram.len();
+        self.prg_ram[offset % len] = value;
+    }
+
+    pub fn save_ram(&self) -> Option<Vec<u8>> {
+        if !self.battery || self.prg_ram.is_empty() {
+            return None;
+        }
+
+        return Some(self.prg_ram.clone());
+    }
+
+    pub fn load_save_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.prg_ram.len());
+        self.prg_ram[..len].copy_from_slice(&data[..len]);
+    }
+}
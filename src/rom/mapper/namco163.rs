@@ -0,0 +1,229 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    Namco 129 and 163 (mapper 19), see https://www.nesdev.org/wiki/Namco_163
+
+    $4800-$4FFF     internal ram data port
+    $5000-$57FF     irq counter low 8 bits, writing acknowledges the irq
+    $5800-$5FFF     irq counter high 7 bits, bit 7 enables the irq
+    $8000-$BFFF     1KB chr banks, one register every $800
+    $C000-$DFFF     1KB nametable banks, one register every $800
+    $E000-$E7FF     8KB prg bank at $8000 in bits 0-5
+    $E800-$EFFF     8KB prg bank at $A000 in bits 0-5, bits 6 and 7 stop $E0-$FF meaning ciram in the chr banks
+    $F000-$F7FF     8KB prg bank at $C000 in bits 0-5, $E000 is fixed to the last bank
+    $F800-$FFFF     internal ram address in bits 0-6 with auto increment in bit 7, also prg ram write protect
+
+    nametable banks $E0-$FF pick ciram page 0 or 1 from bit 0, anything lower maps that 1KB of chr rom in
+    as a read only nametable. Chr banks $E0-$FF are ciram pages the same way, unless $E800 bit 6 (for $0000-$0FFF)
+    or bit 7 (for $1000-$1FFF) is set, then they're chr rom like every other bank.
+
+    the 128 bytes of internal ram hold the sound channel registers on the 163, and some games keep their
+    saves in it. With a battery both are saved, the prg ram followed by the internal ram. The irq counter counts up every cpu cycle and fires when it reaches $7FFF.
+
+    prg ram write protect ($F800), writes only go through when bits 4-7 are $4
+    76543210
+    ||||||||
+    |||||||+- protect $6000-$67FF
+    ||||||+-- protect $6800-$6FFF
+    |||||+--- protect $7000-$77FF
+    ||||+---- protect $7800-$7FFF
+    ++++----- write enable
+*/
+
+#[derive(Clone)]
+pub struct Namco163 {
+    pub cartridge: mapper::Cartridge,
+    pub internal_ram: [u8; 0x80],
+    pub ram_address: u8, // bit 7 is auto increment
+    pub chr_banks: [usize; 8],
+    pub nametable_banks: [usize; 4],
+    pub prg_banks: [usize; 3],
+    pub chr_ciram_disabled: [bool; 2], // per pattern table, $E800 bits 6 and 7
+    pub prg_ram_protect: u8,
+
+    pub irq_counter: u16,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+}
+
+impl Namco163 {
+    pub fn new(rom: &rom::ROM) -> Namco163 {
+        Namco163 {
+            cartridge: mapper::Cartridge::new(rom),
+            internal_ram: [0; 0x80],
+            ram_address: 0,
+            chr_banks: [0; 8],
+            nametable_banks: [0xE0, 0xE1, 0xE0, 0xE1],
+            prg_banks: [0; 3],
+            chr_ciram_disabled: [false; 2],
+            prg_ram_protect: 0,
+
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    fn prg_ram_writable(&self, address: u16) -> bool {
+        let window = ((address - 0x6000) >> 11) & 0x3;
+        return self.prg_ram_protect & 0xF0 == 0x40 && self.prg_ram_protect & (1 << window) == 0;
+    }
+
+    // chr rom bank for a nametable, None when it's ciram
+    fn nametable_bank(&self, address: u16) -> Option<usize> {
+        let bank = self.nametable_banks[(address >> 10) as usize & 0x3];
+        if bank >= 0xE0 {
+            return None;
+        }
+
+        return Some(bank);
+    }
+
+    fn read_internal_ram(&mut self) -> u8 {
+        let value = self.internal_ram[(self.ram_address & 0x7F) as usize];
+        self.increment_ram_address();
+        return value;
+    }
+
+    fn write_internal_ram(&mut self, value: u8) {
+        self.internal_ram[(self.ram_address & 0x7F) as usize] = value;
+        self.increment_ram_address();
+    }
+
+    fn increment_ram_address(&mut self) {
+        if self.ram_address & 0x80 != 0 {
+            self.ram_address = 0x80 | (self.ram_address.wrapping_add(1) & 0x7F);
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address >> 10) as usize & 0x7];
+    }
+}
+
+impl mapper::Mapper for Namco163 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x4800..=0x4FFF => Some(self.read_internal_ram()),
+            0x5000..=0x57FF => Some((self.irq_counter & 0xFF) as u8),
+            0x5800..=0x5FFF => Some(((self.irq_counter >> 8) as u8 & 0x7F) | ((self.irq_enabled as u8) << 7)),
+            0x6000..=0x7FFF => self.cartridge.read_prg_ram(address as usize - 0x6000),
+            0x8000..=0xFFFF => {
+                let window = ((address - 0x8000) >> 13) as usize;
+                let bank = if window < 3 { self.prg_banks[window] } else { self.cartridge.prg_banks(0x2000) - 1 };
+                return Some(self.cartridge.read_prg(0x2000, bank, address as usize & 0x1FFF));
+            },
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4800..=0x4FFF => self.write_internal_ram(value),
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | value as u16;
+                self.irq_pending = false;
+            },
+            0x5800..=0x5FFF => {
+                self.irq_counter = (self.irq_counter & 0x00FF) | (((value & 0x7F) as u16) << 8);
+                self.irq_enabled = value & 0x80 != 0;
+                self.irq_pending = false;
+            },
+            0x6000..=0x7FFF => {
+                if self.prg_ram_writable(address) {
+                    self.cartridge.write_prg_ram(address as usize - 0x6000, value);
+                }
+            },
+            0x8000..=0xBFFF => self.chr_banks[((address - 0x8000) >> 11) as usize] = value as usize,
+            0xC000..=0xDFFF => self.nametable_banks[((address - 0xC000) >> 11) as usize] = value as usize,
+            0xE000..=0xE7FF => self.prg_banks[0] = (value & 0x3F) as usize,
+            0xE800..=0xEFFF => {
+                self.prg_banks[1] = (value & 0x3F) as usize;
+                self.chr_ciram_disabled = [value & 0x40 != 0, value & 0x80 != 0];
+            },
+            0xF000..=0xF7FF => self.prg_banks[2] = (value & 0x3F) as usize,
+            0xF800..=0xFFFF => {
+                self.ram_address = value;
+                self.prg_ram_protect = value;
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        return self.cartridge.read_chr(0x400, self.chr_bank(address), address as usize & 0x3FF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_bank(address);
+        self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+    }
+
+    fn chr_ciram_page(&self, address: u16) -> Option<usize> {
+        let bank = self.chr_bank(address);
+        if bank < 0xE0 || self.chr_ciram_disabled[(address >> 12) as usize & 0x1] {
+            return None;
+        }
+
+        return Some(bank & 0x1);
+    }
+
+    // ciram pages for the nametables that use it, the rom ones are served by nametable_read
+    fn mirroring(&self) -> ppu::Mirroring {
+        let banks = &self.nametable_banks;
+        return ppu::Mirroring::Mapped([banks[0] & 0x1, banks[1] & 0x1, banks[2] & 0x1, banks[3] & 0x1]);
+    }
+
+    fn nametable_read(&mut self, address: u16) -> Option<u8> {
+        let bank = self.nametable_bank(address)?;
+        return Some(self.cartridge.read_chr(0x400, bank, address as usize & 0x3FF));
+    }
+
+    // writes to chr rom nametables go nowhere, they never land in ciram
+    fn nametable_write(&mut self, address: u16, value: u8) -> bool {
+        match self.nametable_bank(address) {
+            Some(bank) => {
+                self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+                return true;
+            },
+            None => return false,
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn cpu_cycle(&mut self) {
+        if !self.irq_enabled || self.irq_counter >= 0x7FFF {
+            return;
+        }
+
+        self.irq_counter += 1;
+        if self.irq_counter == 0x7FFF {
+            self.irq_pending = true;
+        }
+    }
+
+    // prg ram then the internal ram, see the top
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        if !self.cartridge.battery {
+            return None;
+        }
+
+        let mut data = self.cartridge.prg_ram.clone();
+        data.extend_from_slice(&self.internal_ram);
+        return Some(data);
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        let prg_ram_len = data.len().min(self.cartridge.prg_ram.len());
+        self.cartridge.load_save_ram(&data[..prg_ram_len]);
+
+        let internal = &data[prg_ram_len..];
+        let len = internal.len().min(self.internal_ram.len());
+        self.internal_ram[..len].copy_from_slice(&internal[..len]);
+    }
+}
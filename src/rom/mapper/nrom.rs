@@ -0,0 +1,60 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    NROM (mapper 0), no bank switching, see https://www.nesdev.org/wiki/NROM
+
+    $6000-$7FFF     prg ram, family basic only
+    $8000-$BFFF     first 16KB of prg rom
+    $C000-$FFFF     last 16KB of prg rom, or a mirror of the first for NROM-128
+*/
+
+#[derive(Clone)]
+pub struct NROM {
+    pub cartridge: mapper::Cartridge,
+}
+
+impl NROM {
+    pub fn new(rom: &rom::ROM) -> NROM {
+        NROM {
+            cartridge: mapper::Cartridge::new(rom),
+        }
+    }
+}
+
+impl mapper::Mapper for NROM {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x7FFF => self.cartridge.read_prg_ram(address as usize - 0x6000),
+            0x8000..=0xFFFF => Some(self.cartridge.read_prg(0x8000, 0, address as usize - 0x8000)),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        if let 0x6000..=0x7FFF = address {
+            self.cartridge.write_prg_ram(address as usize - 0x6000, value);
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        return self.cartridge.read_chr(0x2000, 0, address as usize);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        self.cartridge.write_chr(0x2000, 0, address as usize, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.cartridge.mirroring;
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::ppu;
+use crate::rom;
+use crate::rom::mapper;
+
+/*
+    Konami VRC2 and VRC4 (mappers 21, 22, 23 and 25), see https://www.nesdev.org/wiki/VRC2_and_VRC4
+
+    $8000-$8003     8KB prg bank at $8000 (or $C000 in prg mode 1)
+    $9000-$9001     mirroring (0: vertical; 1: horizontal; 2: single screen A; 3: single screen B), VRC2 only has bit 0
+    $9002-$9003     VRC4 only, bit 0 enables prg ram and bit 1 is the prg mode
+    $A000-$A003     8KB prg bank at $A000
+    $B000-$E003     1KB chr banks, two registers each, low nibble then the high bits
+    $F000           VRC4 only, irq latch low nibble
+    $F001           VRC4 only, irq latch high nibble
+    $F002           VRC4 only, irq control
+    $F003           VRC4 only, irq acknowledge
+
+    every board wires two cpu address lines to the chip's register select pins, which ones is different from
+    game to game. NES 2.0 submappers say which, plain iNES files get both possible lines of the mapper number ORed.
+
+    mapper  submapper   chip    register lines (A0, A1)
+    21      1           VRC4a   A1, A2
+    21      2           VRC4c   A6, A7
+    22      0           VRC2a   A1, A0 (chr banks are in 2KB units)
+    23      1           VRC4f   A0, A1
+    23      2           VRC4e   A2, A3
+    23      3           VRC2b   A0, A1
+    25      1           VRC4b   A1, A0
+    25      2           VRC4d   A3, A2
+    25      3           VRC2c   A1, A0
+
+    irq control
+    76543210
+         |||
+         ||+- A, irq enable after acknowledge
+         |+-- E, irq enable, writing it with E set also reloads the counter
+         +--- M, irq mode (0: scanline; 1: cpu cycle)
+
+    the irq counter counts up and fires on overflow, reloading from the latch. In scanline mode a prescaler
+    takes 3 off 341 every cpu cycle so it clocks the counter once per 341 ppu dots, there's no ppu snooping.
+*/
+
+#[derive(Clone)]
+pub struct VRC2 {
+    pub cartridge: mapper::Cartridge,
+    pub vrc4: bool,
+    pub register_lines: [u16; 2], // cpu address lines ORed into register select A0 and A1
+    pub chr_shift: usize, // VRC2a leaves out the lowest chr bank line
+    pub prg_banks: [usize; 2],
+    pub prg_mode: bool,
+    pub prg_ram_enabled: bool,
+    pub chr_banks: [usize; 8],
+    pub mirroring: ppu::Mirroring,
+    pub microwire_latch: u8, // VRC2 boards without prg ram read back bit 0 of what's written to $6000-$6FFF
+
+    pub irq_latch: u8,
+    pub irq_counter: u8,
+    pub irq_control: u8,
+    pub irq_prescaler: i16,
+    pub irq_pending: bool,
+}
+
+impl VRC2 {
+    pub fn new(rom: &rom::ROM) -> VRC2 {
+        let submapper = if rom.header.nes_2 { rom.header.submapper } else { 0 };
+
+        // without a submapper 23 and 25 could be either chip, VRC4 does everything VRC2 does
+        let (register_lines, vrc4) = match (rom.header.mapper, submapper) {
+            (21, 1) => ([0x02, 0x04], true),
+            (21, 2) => ([0x40, 0x80], true),
+            (21, _) => ([0x42, 0x84], true),
+            (22, _) => ([0x02, 0x01], false),
+            (23, 1) => ([0x01, 0x02], true),
+            (23, 2) => ([0x04, 0x08], true),
+            (23, 3) => ([0x01, 0x02], false),
+            (23, _) => ([0x05, 0x0A], true),
+            (25, 1) => ([0x02, 0x01], true),
+            (25, 2) => ([0x08, 0x04], true),
+            (25, 3) => ([0x02, 0x01], false),
+            _ => ([0x0A, 0x05], true),
+        };
+
+        VRC2 {
+            cartridge: mapper::Cartridge::new(rom),
+            vrc4: vrc4,
+            register_lines: register_lines,
+            chr_shift: if rom.header.mapper == 22 { 1 } else { 0 },
+            prg_banks: [0, 1],
+            prg_mode: false,
+            prg_ram_enabled: !vrc4,
+            chr_banks: [0; 8],
+            mirroring: rom.header.mirroring(),
+            microwire_latch: 0,
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_control: 0,
+            irq_prescaler: 341,
+            irq_pending: false,
+        }
+    }
+
+    // register select pins for an address, 0-3
+    fn register(&self, address: u16) -> u16 {
+        let a0 = (address & self.register_lines[0] != 0) as u16;
+        let a1 = (address & self.register_lines[1] != 0) as u16;
+        return a0 | (a1 << 1);
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        let second_last = self.cartridge.prg_banks(0x2000).saturating_sub(2);
+
+        match (address >> 13) & 0x3 {
+            0 => if self.prg_mode { second_last } else { self.prg_banks[0] },
+            1 => self.prg_banks[1],
+            2 => if self.prg_mode { self.prg_banks[0] } else { second_last },
+            _ => second_last + 1,
+        }
+    }
+
+    fn write_chr_bank(&mut self, address: u16, register: u16, value: u8) {
+        let slot = (((address - 0xB000) >> 12) * 2 + (register >> 1)) as usize;
+        let value = value as usize;
+
+        if register & 0x1 == 0 {
+            self.chr_banks[slot] = (self.chr_banks[slot] & !0x0F) | (value & 0x0F);
+        } else {
+            let high = if self.vrc4 { value & 0x1F } else { value & 0x0F };
+            self.chr_banks[slot] = (self.chr_banks[slot] & 0x0F) | (high << 4);
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+
+    fn write_irq(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.irq_latch = (self.irq_latch & 0xF0) | (value & 0x0F),
+            1 => self.irq_latch = (self.irq_latch & 0x0F) | (value << 4),
+            2 => {
+                self.irq_control = value & 0x7;
+                self.irq_pending = false;
+                if value & 0x2 != 0 {
+                    self.irq_counter = self.irq_latch;
+                    self.irq_prescaler = 341;
+                }
+            },
+            _ => {
+                // acknowledge, A gets copied into E
+                self.irq_pending = false;
+                self.irq_control = (self.irq_control & !0x2) | ((self.irq_control & 0x1) << 1);
+            },
+        }
+    }
+}
+
+impl mapper::Mapper for VRC2 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        match address {
+            0x6000..=0x6FFF if !self.vrc4 && self.cartridge.prg_ram.is_empty() => {
+                return Some(((address >> 8) as u8 & 0xFE) | self.microwire_latch);
+            },
+            0x6000..=0x7FFF => {
+                if !self.prg_ram_enabled {
+                    return None;
+                }
+                return self.cartridge.read_prg_ram(address as usize - 0x6000);
+            },
+            0x8000..=0xFFFF => Some(self.cartridge.read_prg(0x2000, self.prg_bank(address), address as usize & 0x1FFF)),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        let register = self.register(address);
+
+        match address {
+            0x6000..=0x6FFF if !self.vrc4 && self.cartridge.prg_ram.is_empty() => self.microwire_latch = value & 0x1,
+            0x6000..=0x7FFF => {
+                if self.prg_ram_enabled {
+                    self.cartridge.write_prg_ram(address as usize - 0x6000, value);
+                }
+            },
+            0x8000..=0x8FFF => self.prg_banks[0] = (value & 0x1F) as usize,
+            0x9000..=0x9FFF if register >= 2 && self.vrc4 => {
+                self.prg_ram_enabled = value & 0x1 != 0;
+                self.prg_mode = value & 0x2 != 0;
+            },
+            0x9000..=0x9FFF => {
+                let value = if self.vrc4 { value & 0x3 } else { value & 0x1 };
+                self.mirroring = match value {
+                    0 => ppu::Mirroring::Vertical,
+                    1 => ppu::Mirroring::Horizontal,
+                    2 => ppu::Mirroring::SingleScreenA,
+                    _ => ppu::Mirroring::SingleScreenB,
+                };
+            },
+            0xA000..=0xAFFF => self.prg_banks[1] = (value & 0x1F) as usize,
+            0xB000..=0xEFFF => self.write_chr_bank(address & 0xF000, register, value),
+            0xF000..=0xFFFF if self.vrc4 => self.write_irq(register, value),
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        let bank = self.chr_banks[(address >> 10) as usize & 0x7] >> self.chr_shift;
+        return self.cartridge.read_chr(0x400, bank, address as usize & 0x3FF);
+    }
+
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        let bank = self.chr_banks[(address >> 10) as usize & 0x7] >> self.chr_shift;
+        self.cartridge.write_chr(0x400, bank, address as usize & 0x3FF, value);
+    }
+
+    fn mirroring(&self) -> ppu::Mirroring {
+        return self.mirroring;
+    }
+
+    fn irq_pending(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.irq_control & 0x2 == 0 {
+            return;
+        }
+
+        if self.irq_control & 0x4 != 0 {
+            self.clock_irq_counter();
+            return;
+        }
+
+        self.irq_prescaler -= 3;
+        if self.irq_prescaler <= 0 {
+            self.irq_prescaler += 341;
+            self.clock_irq_counter();
+        }
+    }
+
+    fn save_ram(&self) -> Option<Vec<u8>> {
+        return self.cartridge.save_ram();
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        self.cartridge.load_save_ram(data);
+    }
+}
@@ -1,10 +1,13 @@
 use std::io;
 use std::io::Read;
 use std::io::BufReader;
+use std::fs;
 use std::fs::File;
 use std::fmt;
+use std::path::Path;
 use crate::config;
-mod mapper;
+use crate::ppu;
+pub mod mapper;
 
 // see structure here https://www.nesdev.org/wiki/INES
 pub struct ROM {
@@ -14,6 +17,7 @@ pub struct ROM {
     pub chr_rom: Vec<u8>,
     pub playchoice_inst_rom: [u8; 0x2000], // only used if playchoice stuff header is set
     pub playchoice_prom: [u8; 0x20], // only used if playchoice stuff header is set
+    pub mmc3_revision: Option<mapper::mmc3::Revision>, // from the command line, overrides the submapper
 }
 
 impl Default for ROM {
@@ -25,6 +29,7 @@ impl Default for ROM {
             chr_rom: Vec::new(),
             playchoice_inst_rom: [0; 0x2000],
             playchoice_prom: [0; 0x20],
+            mmc3_revision: None,
         }
     }
 }
@@ -44,10 +49,12 @@ pub struct Header {
     pub vertical_mirroring: bool,
     pub persistent_memory: bool,
     pub trainer: bool,
-    pub ignore_mirroring: bool,
+    pub ignore_mirroring: bool, // four screen vram when set
     
     // mapper number, lower nibble from flags 6 then upper nibble from flag 7 (both are in upper 4 bits dont forget)
-    pub mapper: u8,
+    // nes 2.0 adds 4 more bits in byte 8
+    pub mapper: u16,
+    pub submapper: u8, // nes 2.0 only, board variant of the mapper
 
     // lower 4 bits of flags 7
     pub vs_unisystem: bool,
@@ -68,19 +75,27 @@ pub struct Header {
     pub bus_conflicts: bool,
     // 2 more empty bits
 
+    // nes 2.0 bytes 10 and 11, ram sizes as shift counts (64 << n bytes), volatile in the low nibble and battery backed in the high
+    pub prg_ram_shifts: u8,
+    pub chr_ram_shifts: u8,
+
+    // nes 2.0 byte 15, the input device the game expects
+    pub expansion_device: u8,
+
     // pub padding: [u8; 0x5],
 }
 
 impl fmt::Display for Header {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\t\tprg_rom_length: {}\n\t\tchr_rom_length: {}\n\t\tvertical_mirroring: {}\n\t\tpersistent_memory: {}\n\t\ttrainer: {}\n\t\tignore_mirroring: {}\n\t\tmapper: {}",
+        write!(f, "\t\tprg_rom_length: {}\n\t\tchr_rom_length: {}\n\t\tvertical_mirroring: {}\n\t\tpersistent_memory: {}\n\t\ttrainer: {}\n\t\tignore_mirroring: {}\n\t\tmapper: {}\n\t\tsubmapper: {}",
             self.prg_rom_length,
             self.chr_rom_length,
             self.vertical_mirroring,
             self.persistent_memory,
             self.trainer,
             self.ignore_mirroring,
-            self.mapper)
+            self.mapper,
+            self.submapper)
     }
 }
 
@@ -95,6 +110,7 @@ impl Default for Header {
             trainer: false,
             ignore_mirroring: false,
             mapper: 0,
+            submapper: 0,
             vs_unisystem: false,
             play_choice_10: false,
             nes_2: false,
@@ -103,8 +119,52 @@ impl Default for Header {
             tv_system: 0,
             has_prg_ram: false,
             bus_conflicts: false,
+            prg_ram_shifts: 0,
+            chr_ram_shifts: 0,
+            expansion_device: 0,
+        }
+    }
+}
+
+impl Header {
+    // mirroring the board is hardwired to, mappers with mirroring control can change it at runtime
+    pub fn mirroring(&self) -> ppu::Mirroring {
+        if self.ignore_mirroring {
+            return ppu::Mirroring::FourScreen;
+        }
+
+        if self.vertical_mirroring {
+            return ppu::Mirroring::Vertical;
+        }
+
+        return ppu::Mirroring::Horizontal;
+    }
+
+    // ines 1.0 files often leave the prg ram size as 0, which means 8KB
+    pub fn prg_ram_bytes(&self) -> usize {
+        if self.nes_2 {
+            return shifted_size(self.prg_ram_shifts & 0xF) + shifted_size(self.prg_ram_shifts >> 4);
+        }
+
+        return (self.prg_ram_size as usize).max(1) * 0x2000;
+    }
+
+    // only used when there's no chr rom
+    pub fn chr_ram_bytes(&self) -> usize {
+        if self.nes_2 && self.chr_ram_shifts != 0 {
+            return shifted_size(self.chr_ram_shifts & 0xF) + shifted_size(self.chr_ram_shifts >> 4);
         }
+
+        return 0x2000;
+    }
+}
+
+fn shifted_size(shift: u8) -> usize {
+    if shift == 0 {
+        return 0;
     }
+
+    return 64 << shift;
 }
 
 impl ROM {
@@ -131,11 +191,13 @@ impl ROM {
             memory_counter += 0x200 as u32;
         }
         
-        self.prg_rom.extend_from_slice(&buffer[memory_counter as usize..(memory_counter - 1 + (0x4000 * self.header.prg_rom_length as u32)) as usize]);
-        memory_counter += 0x4000 + self.header.prg_rom_length as u32;
+        let prg_rom_size = 0x4000 * self.header.prg_rom_length as u32;
+        self.prg_rom.extend_from_slice(&buffer[memory_counter as usize..(memory_counter + prg_rom_size) as usize]);
+        memory_counter += prg_rom_size;
 
-        self.chr_rom.extend_from_slice(&buffer[memory_counter as usize..(memory_counter - 1 + (0x2000 * self.header.chr_rom_length as u32)) as usize]);
-        // memory_counter += 0x2000 + self.header.chr_rom_length as u32;
+        let chr_rom_size = 0x2000 * self.header.chr_rom_length as u32;
+        self.chr_rom.extend_from_slice(&buffer[memory_counter as usize..(memory_counter + chr_rom_size) as usize]);
+        // memory_counter += chr_rom_size;
 
         // TODO: playchoice stuff here once I read more on it
     }
@@ -151,7 +213,7 @@ impl ROM {
         let flags9: u8 = buffer[9];
         let flags10: u8 = buffer[10];
 
-        self.header.mapper = ((flags6 & 0xF0) >> 4) | (flags7 & 0xF0);
+        self.header.mapper = (((flags6 & 0xF0) >> 4) | (flags7 & 0xF0)) as u16;
 
         self.header.vertical_mirroring  = flags6 & 0x1 == 0x1;
         self.header.persistent_memory  = flags6 & 0x2 == 0x2;
@@ -169,17 +231,100 @@ impl ROM {
         self.header.tv_system = (flags10 & 0x1) | ((flags10 & 0x2) << 1);
         self.header.has_prg_ram = flags10 & 0x10 == 0x0;
         self.header.bus_conflicts = flags10 & 0x20 == 0x20;
+
+        if self.header.nes_2 {
+            self.header.mapper |= ((flags8 & 0x0F) as u16) << 8;
+            self.header.submapper = flags8 >> 4;
+            self.header.prg_ram_shifts = buffer[10];
+            self.header.chr_ram_shifts = buffer[11];
+            self.header.expansion_device = buffer[15] & 0x3F;
+        }
+    }
+}
+
+pub fn init_mapper(rom: &ROM) -> Result<Box<dyn mapper::Mapper>, mapper::Error> {
+    return mapper::new(rom);
+}
+
+// cartridge space, these go to the mapper. Nothing answers before one is loaded
+pub fn cpu_read(emulator: &mut config::Emulator, address: u16) -> Option<u8> {
+    return emulator.mapper.as_mut().and_then(|mapper| mapper.cpu_read(address));
+}
+
+pub fn cpu_write(emulator: &mut config::Emulator, address: u16, value: u8) {
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.cpu_write(address, value);
     }
 }
 
-fn get_mapper(emulator: &config::Emulator) -> (u8, fn(&mut config::Emulator)) {
-    let mut mapper_iterator = mapper::MAPPERS.iter();
+pub fn ppu_read(emulator: &mut config::Emulator, address: u16) -> u8 {
+    return emulator.mapper.as_mut().map_or(0, |mapper| mapper.ppu_read(address));
+}
+
+pub fn ppu_write(emulator: &mut config::Emulator, address: u16, value: u8) {
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.ppu_write(address, value);
+    }
+}
+
+pub fn chr_ciram_page(emulator: &config::Emulator, address: u16) -> Option<usize> {
+    return emulator.mapper.as_ref().and_then(|mapper| mapper.chr_ciram_page(address));
+}
+
+pub fn nametable_read(emulator: &mut config::Emulator, address: u16) -> Option<u8> {
+    return emulator.mapper.as_mut().and_then(|mapper| mapper.nametable_read(address));
+}
+
+pub fn nametable_write(emulator: &mut config::Emulator, address: u16, value: u8) -> bool {
+    return emulator.mapper.as_mut().map_or(false, |mapper| mapper.nametable_write(address, value));
+}
+
+pub fn ppu_register_write(emulator: &mut config::Emulator, address: u16, value: u8) {
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.ppu_register_write(address, value);
+    }
+}
+
+pub fn mirroring(emulator: &config::Emulator) -> ppu::Mirroring {
+    return emulator.mapper.as_ref().map_or(emulator.rom.header.mirroring(), |mapper| mapper.mirroring());
+}
 
-    // we unwrap the find here so it crashes if the mapper is invalid, for now
-    return *mapper_iterator.find(|&x| x.0 == emulator.rom.header.mapper).unwrap();
+pub fn irq_pending(emulator: &config::Emulator) -> bool {
+    return emulator.mapper.as_ref().map_or(false, |mapper| mapper.irq_pending());
 }
 
-pub fn init_mapper(emulator: &mut config::Emulator) {
-    let mapper = get_mapper(emulator);
-    mapper.1(emulator);
-}
\ No newline at end of file
+pub fn cpu_cycle(emulator: &mut config::Emulator) {
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.cpu_cycle();
+    }
+}
+
+pub fn ppu_cycle(emulator: &mut config::Emulator) {
+    let (scanline, cycle) = (emulator.ppu.scanline, emulator.ppu.cycle);
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.ppu_cycle(scanline, cycle);
+    }
+}
+
+// battery backed ram is kept next to the rom as <rom name>.sav
+pub fn load_save_ram(emulator: &mut config::Emulator, file_name: &String) -> io::Result<()> {
+    let path = Path::new(file_name).with_extension("sav");
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let data = fs::read(&path)?;
+    if let Some(mapper) = emulator.mapper.as_mut() {
+        mapper.load_save_ram(&data);
+    }
+
+    Ok(())
+}
+
+pub fn write_save_ram(emulator: &config::Emulator, file_name: &String) -> io::Result<()> {
+    if let Some(data) = emulator.mapper.as_ref().and_then(|mapper| mapper.save_ram()) {
+        fs::write(Path::new(file_name).with_extension("sav"), data)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use crate::apu;
+use crate::config;
+use crate::cpu;
+use crate::ppu;
+use crate::rom;
+
+/*
+    save states, a snapshot of everything the running game can change: cpu, ppu, apu and the mapper with
+    its banks, ram and irq counters. The rom and the plugged in devices aren't part of it, a state only
+    makes sense for the rom it was saved from and the devices follow whoever is holding them.
+
+    states are kept in memory for the session, they're taken and restored between frames from the hotkeys.
+*/
+
+pub struct SaveState {
+    pub cpu: cpu::CPU,
+    pub ppu: ppu::PPU,
+    pub apu: apu::APU,
+    pub mapper: Option<Box<dyn rom::mapper::Mapper>>,
+}
+
+pub fn save(emulator: &config::Emulator) -> SaveState {
+    return SaveState {
+        cpu: emulator.cpu.clone(),
+        ppu: emulator.ppu.clone(),
+        apu: emulator.apu.clone(),
+        mapper: emulator.mapper.clone(),
+    };
+}
+
+pub fn load(emulator: &mut config::Emulator, state: &SaveState) {
+    emulator.cpu = state.cpu.clone();
+    emulator.ppu = state.ppu.clone();
+    emulator.apu = state.apu.clone();
+    emulator.mapper = state.mapper.clone();
+}